@@ -13,13 +13,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use azalia::{
-    log::{WriteLayer, writers},
-    remi::StorageService,
-};
+use axum::http::Method;
+use azalia::log::{WriteLayer, writers};
+use eyre::eyre;
 use hazel::{
-    config::{Config, storage},
-    server,
+    config::Config,
+    logging,
+    server::{self, canonical::CanonicalPath},
+    signing::{self, Grant},
+    storage::Storage,
+    telemetry,
 };
 use mimalloc::MiMalloc;
 use sentry::ClientOptions;
@@ -27,8 +30,9 @@ use std::{
     borrow::Cow,
     cmp, io,
     sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
 };
-use tracing::{info, level_filters::LevelFilter};
+use tracing::{info, warn};
 use tracing_subscriber::prelude::*;
 
 #[global_allocator]
@@ -37,6 +41,13 @@ static GLOBAL: MiMalloc = MiMalloc;
 fn main() -> eyre::Result<()> {
     dotenvy::dotenv().unwrap_or_default();
 
+    // any other arguments are ignored like they always have been, so that existing
+    // invocations keep starting the server
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("sign") {
+        return sign(args);
+    }
+
     let workers = cmp::max(
         num_cpus::get(),
         azalia::config::env::try_parse_or("HAZEL_WORKER_THREADS", num_cpus::get)?,
@@ -56,6 +67,76 @@ fn main() -> eyre::Result<()> {
     rt.block_on(real_main())
 }
 
+/// `hazel sign <path> [--expires-in SECS] [--key ID] [--method METHOD] [--ip CIDR]
+/// [--range RANGE] [--base-url URL]`: prints a signed URL to `path` with the keys from
+/// the `[server.signing]` table.
+fn sign(mut args: impl Iterator<Item = String>) -> eyre::Result<()> {
+    let mut path = None;
+    let mut expires_in = Duration::from_secs(3600);
+    let mut key = None;
+    let mut method = Method::GET;
+    let mut ip = None;
+    let mut range = None;
+    let mut base_url = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| eyre!("missing value for `{arg}`"));
+        match arg.as_str() {
+            "--expires-in" => expires_in = Duration::from_secs(value()?.parse()?),
+            "--key" => key = Some(value()?),
+            "--method" => method = Method::from_bytes(value()?.to_ascii_uppercase().as_bytes())?,
+            "--ip" => {
+                let value = value()?;
+                ip = Some(signing::parse_ip(&value).ok_or_else(|| eyre!("invalid ip or cidr range `{value}`"))?);
+            }
+            "--range" => range = Some(value()?),
+            "--base-url" => base_url = Some(value()?),
+            flag if flag.starts_with("--") => return Err(eyre!("unknown flag `{flag}`")),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(eyre!("unexpected argument `{arg}`")),
+        }
+    }
+
+    let path = path.ok_or_else(|| eyre!("usage: hazel sign <path> [--expires-in SECS] [--key ID] [--method METHOD] [--ip CIDR] [--range RANGE] [--base-url URL]"))?;
+    let config = Config::new()?;
+    let signing = config
+        .server
+        .signing
+        .as_ref()
+        .ok_or_else(|| eyre!("signed urls are not enabled; configure the `[server.signing]` table"))?;
+
+    let (key, secret) = match key {
+        Some(ref id) => signing
+            .keys
+            .get_key_value(id)
+            .map(|(id, secret)| (id.as_str(), secret.as_str()))
+            .ok_or_else(|| eyre!("unknown signing key `{id}`"))?,
+
+        None => signing
+            .signing_key()
+            .ok_or_else(|| eyre!("no signing keys are configured"))?,
+    };
+
+    // the path can be given decoded or as it appears in a URL, since both have the same
+    // canonical path that requests are verified against
+    let path = CanonicalPath::parse(&path).map_err(|e| eyre!("invalid path `{path}`: {e}"))?;
+    let grant = Grant {
+        method,
+        ip,
+        range,
+        ..Grant::new(path.to_string(), expires_in)
+    };
+
+    let query = grant.sign(key, secret.as_bytes());
+    let path = path.encode();
+    match base_url {
+        Some(url) => println!("{}{path}?{query}", url.trim_end_matches('/')),
+        None => println!("{path}?{query}"),
+    }
+
+    Ok(())
+}
+
 async fn real_main() -> eyre::Result<()> {
     let config = Config::new()?;
     color_eyre::install()?;
@@ -76,21 +157,26 @@ async fn real_main() -> eyre::Result<()> {
         ..Default::default()
     });
 
+    let (otel, tracer_provider) = config
+        .tracing
+        .opentelemetry
+        .as_ref()
+        .map(|otel| telemetry::init(otel, config.logging.level))
+        .transpose()?
+        .unzip();
+
     tracing_subscriber::registry()
         .with(
             match config.logging.json {
                 false => WriteLayer::new_with(io::stdout(), writers::default::Writer::default()),
                 true => WriteLayer::new_with(io::stdout(), writers::json),
             }
-            .with_filter(LevelFilter::from_level(config.logging.level))
-            .with_filter(tracing_subscriber::filter::filter_fn(|meta| {
-                // disallow from getting logs from `tokio` since it doesn't contain anything
-                // useful to us
-                !meta.target().starts_with("tokio::")
-            })),
+            .with_filter(logging::filter(&config.logging, None)?),
         )
+        .with(logging::sinks(&config.logging)?)
         .with(sentry_tracing::layer())
         .with(tracing_error::ErrorLayer::default())
+        .with(otel)
         .init();
 
     info!(
@@ -100,17 +186,20 @@ async fn real_main() -> eyre::Result<()> {
         hazel::RUSTC
     );
 
-    let storage = match config.storage.clone() {
-        storage::Config::Filesystem(fs) => {
-            StorageService::Filesystem(azalia::remi::fs::StorageService::with_config(fs))
-        }
-
-        storage::Config::Azure(azure) => StorageService::Azure(azalia::remi::azure::StorageService::new(azure)?),
-        storage::Config::S3(s3) => StorageService::S3(azalia::remi::s3::StorageService::new(s3)),
-    };
-
-    <StorageService as azalia::remi::core::StorageService>::init(&storage).await?;
+    let storage = Storage::new(config.storage.clone())?;
+    storage.init().await?;
     info!("data storage has been initialized");
 
-    server::start(storage, config).await
+    let result = server::start(storage, config).await;
+    if let Some(provider) = tracer_provider {
+        // flushing the spans that haven't been exported yet blocks until the exporter
+        // is done with them
+        match tokio::task::spawn_blocking(move || provider.shutdown()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!(error = %e, "failed to flush opentelemetry spans"),
+            Err(e) => warn!(error = %e, "failed to flush opentelemetry spans"),
+        }
+    }
+
+    result
 }
@@ -13,11 +13,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod host;
 pub mod logging;
+pub mod mount;
 pub mod opentelemetry;
 pub mod server;
 pub mod storage;
-pub(in crate::config) mod util;
+pub mod tracing;
+pub(crate) mod util;
 
 use azalia::config::{
     env::{self, TryFromEnv},
@@ -26,7 +29,7 @@ use azalia::config::{
 use eyre::Report;
 use sentry::types::Dsn;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf};
+use std::{collections::BTreeMap, fs, path::PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Merge)]
 pub struct Config {
@@ -50,9 +53,25 @@ pub struct Config {
     #[serde(default)]
     pub storage: storage::Config,
 
+    /// Storage backends that serve the objects under a path prefix instead of
+    /// [`Config::storage`], keyed by the prefix. The mount with the longest prefix that
+    /// a path is in is used.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mounts: BTreeMap<String, mount::Mount>,
+
+    /// Virtual hosts that are served from storage backends of their own, keyed by the
+    /// name that the `Host` header is matched against. Requests for any other host are
+    /// served with the top-level configuration.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hosts: BTreeMap<String, host::Host>,
+
     /// Configures the HTTP server's host and port bindings and SSL.
     #[serde(default)]
     pub server: server::Config,
+
+    /// Configures exporting the spans of each request.
+    #[serde(default)]
+    pub tracing: tracing::Config,
 }
 
 pub const SERVER_NAME: &str = "HAZEL_SERVER_NAME";
@@ -67,7 +86,10 @@ impl TryFromEnv for Config {
             sentry_dsn: env::try_parse_optional::<_, sentry::types::Dsn>(SENTRY_DSN)?,
             logging: logging::Config::try_from_env()?,
             storage: storage::Config::try_from_env()?,
+            mounts: BTreeMap::new(),
+            hosts: BTreeMap::new(),
             server: server::Config::try_from_env()?,
+            tracing: tracing::Config::try_from_env()?,
         })
     }
 }
@@ -95,6 +117,39 @@ impl Config {
     }
 
     pub fn new() -> eyre::Result<Config> {
+        let config = Config::load()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks the settings that can't be checked while they're deserialized, like the
+    /// ones that depend on each other.
+    pub fn validate(&self) -> eyre::Result<()> {
+        for rule in &self.server.access_rules {
+            rule.validate()?;
+        }
+
+        for rule in &self.server.rate_limits {
+            rule.validate()?;
+        }
+
+        for realm in &self.server.basic_auth {
+            realm.validate()?;
+        }
+
+        if let Some(ref jwt) = self.server.jwt {
+            jwt.validate()?;
+        }
+
+        if let Some(ref signing) = self.server.signing {
+            signing.validate()?;
+        }
+
+        Ok(())
+    }
+
+    fn load() -> eyre::Result<Config> {
         let Some(path) = Config::find_default_location() else {
             return Config::try_from_env();
         };
@@ -109,7 +164,10 @@ impl Config {
         }
 
         let mut config = Config::try_from_env()?;
-        let file = toml::from_str(fs::read_to_string(path)?.as_str())?;
+        let contents = fs::read_to_string(path)?;
+        host::reject_global_settings(&contents.parse()?)?;
+
+        let file = toml::from_str(&contents)?;
         config.merge(file);
 
         Ok(config)
@@ -139,3 +197,39 @@ macro_rules! impl_enum_based_env_value {
 }
 
 pub(crate) use impl_enum_based_env_value;
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    fn validate(toml: &str) -> eyre::Result<()> {
+        let toml = format!("[storage.filesystem]\ndirectory = \"/tmp\"\n\n{toml}");
+        toml::from_str::<Config>(&toml).unwrap().validate()
+    }
+
+    #[test]
+    fn test_validate_prefixes() {
+        assert!(validate("[server.jwt]\nprefixes = [\"/private\"]").is_ok());
+        assert!(validate("[[server.access_rules]]\naction = \"deny\"\nprefixes = [\"internal\"]").is_err());
+        assert!(validate("[[server.rate_limits]]\nprefixes = [\"api\"]").is_err());
+        assert!(validate("[[server.basic_auth]]\nprefixes = [\"internal\"]\nhtpasswd = \"x\"").is_err());
+
+        let error = validate("[server.jwt]\nprefixes = [\"private\"]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`server.jwt.prefixes` has `private`, but every prefix has to start with a `/`"
+        );
+    }
+
+    #[test]
+    fn test_validate_rate_limits() {
+        let rule = |settings: &str| validate(&format!("[[server.rate_limits]]\n{settings}"));
+
+        assert!(rule("requests_per_second = 0.5\nbytes_per_second = 1").is_ok());
+        assert!(rule("requests_per_second = 0.0").is_err());
+        assert!(rule("requests_per_second = -1.0").is_err());
+        assert!(rule("requests_per_second = inf").is_err());
+        assert!(rule("requests_per_second = nan").is_err());
+        assert!(rule("bytes_per_second = 0").is_err());
+    }
+}
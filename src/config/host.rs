@@ -0,0 +1,142 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::{
+    mount,
+    server::{self, errors},
+    storage,
+};
+use azalia::config::merge::Merge;
+use eyre::bail;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Settings of `[server]` that are applied to every request before its host is known,
+/// so they can't be set for a single host.
+const GLOBAL_SETTINGS: &[&str] =
+    &["compression", "access_rules", "rate_limits", "basic_auth", "jwt", "signing", "access_log"];
+
+/// ## `[hosts."<name>"]` table
+/// Serves the requests whose `Host` header matches the name from a storage backend of
+/// their own, with their own headers and error pages. The name can start with a `*.`
+/// wildcard to match every subdomain, like `*.example.com`.
+///
+/// ```toml
+/// [hosts."docs.example.com"]
+/// index_files = ["index.html"]
+///
+/// [hosts."docs.example.com".storage.s3]
+/// bucket = "docs"
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Host {
+    /// The storage backend that the host's objects are served from.
+    pub storage: storage::Config,
+
+    /// Headers to set on the host's responses, on top of `server.headers`. Headers with
+    /// the same name replace the ones from `server.headers`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// Replaces `server.errors` for this host. Error documents are located in this
+    /// host's storage backend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errors: Option<errors::Config>,
+
+    /// Overrides `server.index_files` for this host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_files: Option<Vec<String>>,
+
+    /// Overrides `server.fallback` for this host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+
+    /// Storage backends that serve the objects under a path prefix of this host. The
+    /// top-level `[mounts]` aren't used for virtual hosts.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mounts: BTreeMap<String, mount::Mount>,
+}
+
+impl Host {
+    /// Applies the settings that this host overrides to `server`.
+    pub fn apply(&self, server: &mut server::Config) {
+        server
+            .headers
+            .extend(self.headers.iter().map(|(name, value)| (name.clone(), value.clone())));
+
+        if let Some(ref errors) = self.errors {
+            server.errors = errors.clone();
+        }
+
+        if let Some(ref index_files) = self.index_files {
+            server.index_files = index_files.clone();
+        }
+
+        if let Some(ref fallback) = self.fallback {
+            server.fallback = Some(fallback.clone());
+        }
+    }
+}
+
+/// Rejects the settings in the `[hosts]` of a configuration file that only `[server]`
+/// supports, which would otherwise be reported as unknown fields without saying why.
+pub fn reject_global_settings(file: &toml::Table) -> eyre::Result<()> {
+    let Some(toml::Value::Table(hosts)) = file.get("hosts") else {
+        return Ok(());
+    };
+
+    for (name, host) in hosts {
+        let Some(host) = host.as_table() else {
+            continue;
+        };
+
+        if host.contains_key("server") {
+            bail!(
+                "`hosts.\"{name}\".server` can't be set: virtual hosts use `[server]`, only the settings under `[hosts.\"{name}\"]` can be overridden"
+            );
+        }
+
+        if let Some(key) = GLOBAL_SETTINGS.iter().find(|key| host.contains_key(**key)) {
+            bail!("`hosts.\"{name}\".{key}` can't be set for a single host, `server.{key}` applies to every host");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reject_global_settings;
+
+    #[test]
+    fn test_reject_global_settings() {
+        let file = |toml: &str| toml.parse::<toml::Table>().unwrap();
+
+        assert!(reject_global_settings(&file("[hosts.\"a.example.com\"]\nindex_files = []")).is_ok());
+
+        let error =
+            reject_global_settings(&file("[hosts.\"a.example.com\".compression]\nmin_size = 1")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`hosts.\"a.example.com\".compression` can't be set for a single host, `server.compression` applies to every host"
+        );
+
+        assert!(
+            reject_global_settings(&file("[[hosts.\"a.example.com\".basic_auth]]\nhtpasswd = \"x\"")).is_err()
+        );
+        assert!(reject_global_settings(&file("[hosts.\"a.example.com\".server.jwt]\nkeys = []")).is_err());
+    }
+}
@@ -13,12 +13,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod file;
+pub mod journald;
+pub mod syslog;
+
 use crate::config::util;
 use azalia::config::{
     env::{self, TryFromEnv, TryFromEnvValue},
     merge::Merge,
 };
 use serde::{Deserialize, Deserializer, Serialize};
+use std::{fmt::Display, str::FromStr};
 use tracing::Level;
 use tracing_subscriber::{EnvFilter, filter};
 
@@ -90,6 +95,18 @@ pub struct Config {
     #[serde(default)]
     #[merge(strategy = azalia::config::merge::strategy::bool::only_if_falsy)]
     pub json: bool,
+
+    /// Writes logs to a rotating file as well. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<file::Config>,
+
+    /// Sends logs to a syslog server as well. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syslog: Option<syslog::Config>,
+
+    /// Sends logs to the systemd journal as well. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub journald: Option<journald::Config>,
 }
 
 impl Default for Config {
@@ -98,6 +115,9 @@ impl Default for Config {
             filter: None,
             level: __default_level(),
             json: false,
+            file: None,
+            syslog: None,
+            journald: None,
         }
     }
 }
@@ -110,6 +130,21 @@ impl TryFromEnv for Config {
             filter: env::try_parse_optional(FILTER)?,
             json: util::bool_env(JSON)?,
             level: env::try_parse_or(LEVEL, __default_level)?,
+            file: match util::bool_env(file::ENABLED) {
+                Ok(true) => file::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            syslog: match util::bool_env(syslog::ENABLED) {
+                Ok(true) => syslog::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            journald: match util::bool_env(journald::ENABLED) {
+                Ok(true) => journald::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
         })
     }
 }
@@ -117,3 +152,129 @@ impl TryFromEnv for Config {
 const fn __default_level() -> Level {
     Level::INFO
 }
+
+/// Configures when a log file is rotated, and how many of the rotated files are kept.
+/// Rotated files are renamed to `<file>.<timestamp>` next to the file.
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rotation {
+    /// Size in bytes that the file is rotated at. If `0`, then files aren't rotated
+    /// by their size.
+    #[serde(default = "__default_max_size")]
+    pub max_size: u64,
+
+    /// How often the file is rotated, regardless of its size.
+    #[serde(default)]
+    pub interval: Interval,
+
+    /// Amount of rotated files that are kept; older ones are deleted. If `0`, then all
+    /// of them are kept.
+    #[serde(default = "__default_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Rotation {
+            max_size: __default_max_size(),
+            interval: Interval::default(),
+            max_files: __default_max_files(),
+        }
+    }
+}
+
+impl Rotation {
+    /// Loads the rotation settings from the `{prefix}_MAX_SIZE`, `{prefix}_INTERVAL`
+    /// and `{prefix}_MAX_FILES` environment variables.
+    pub(crate) fn from_env(prefix: &str) -> eyre::Result<Self> {
+        Ok(Rotation {
+            max_size: util::env_from_str(&format!("{prefix}_MAX_SIZE"), __default_max_size())?,
+            interval: util::env_from_str(&format!("{prefix}_INTERVAL"), Interval::default())?,
+            max_files: util::env_from_str(&format!("{prefix}_MAX_FILES"), __default_max_files())?,
+        })
+    }
+}
+
+/// How often a log file is rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    /// Files are only rotated by their size.
+    Never,
+
+    /// Files are rotated at the start of every hour (UTC).
+    Hourly,
+
+    /// Files are rotated at midnight (UTC).
+    #[default]
+    Daily,
+}
+
+impl Interval {
+    /// Returns the length of this interval in seconds.
+    pub fn as_secs(self) -> Option<i64> {
+        match self {
+            Interval::Never => None,
+            Interval::Hourly => Some(60 * 60),
+            Interval::Daily => Some(24 * 60 * 60),
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.trim().to_ascii_lowercase() {
+            "never" => Ok(Interval::Never),
+            "hourly" => Ok(Interval::Hourly),
+            "daily" => Ok(Interval::Daily),
+            input => Err(format!(
+                "unknown rotation interval `{input}`: expected `never`, `hourly` or `daily`"
+            )),
+        }
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interval::Never => f.write_str("never"),
+            Interval::Hourly => f.write_str("hourly"),
+            Interval::Daily => f.write_str("daily"),
+        }
+    }
+}
+
+impl Merge for Interval {
+    fn merge(&mut self, other: Self) {
+        if other != Interval::default() {
+            *self = other;
+        }
+    }
+}
+
+const fn __default_max_size() -> u64 {
+    100 * 1024 * 1024
+}
+
+const fn __default_max_files() -> usize {
+    7
+}
+
+/// (De)serializes the optional level of a sink, which falls back to [`Config::level`].
+pub(crate) mod optional_level {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use tracing::Level;
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "azalia::serde::tracing")] Level);
+
+    pub fn serialize<S: Serializer>(level: &Option<Level>, serializer: S) -> Result<S::Ok, S::Error> {
+        level.map(Wrapper).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Level>, D::Error> {
+        Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(level)| level))
+    }
+}
@@ -0,0 +1,92 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::{
+    logging::{Rotation, optional_level},
+    util,
+};
+use azalia::config::{
+    env::{self, TryFromEnv},
+    merge::Merge,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::Level;
+
+pub const ENABLED: &str = "HAZEL_LOG_FILE";
+pub const PATH: &str = "HAZEL_LOG_FILE_PATH";
+pub const LEVEL: &str = "HAZEL_LOG_FILE_LEVEL";
+pub const JSON: &str = "HAZEL_LOG_FILE_JSON";
+pub const ROTATION: &str = "HAZEL_LOG_FILE_ROTATION";
+
+/// ## `[logging.file]` table
+/// Writes logs to a file that is rotated by its size or on an interval.
+///
+/// ```toml
+/// [logging.file]
+/// path = "/var/log/hazel/hazel.log"
+/// level = "debug"
+/// json = true
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Path to the file that logs are written to. Its directory is created if it
+    /// doesn't exist.
+    #[serde(default = "__default_path")]
+    pub path: PathBuf,
+
+    /// Most verbose level that is written to the file. Defaults to `logging.level`.
+    #[serde(default, with = "optional_level", skip_serializing_if = "Option::is_none")]
+    pub level: Option<Level>,
+
+    /// whether or not to write the logs as JSON objects.
+    #[serde(default)]
+    #[merge(strategy = azalia::config::merge::strategy::bool::only_if_falsy)]
+    pub json: bool,
+
+    /// Configures when the file is rotated.
+    #[serde(default)]
+    pub rotation: Rotation,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            path: __default_path(),
+            level: None,
+            json: false,
+            rotation: Rotation::default(),
+        }
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            path: util::env_from_str(PATH, __default_path())?,
+            level: env::try_parse_optional(LEVEL)?,
+            json: util::bool_env(JSON)?,
+            rotation: Rotation::from_env(ROTATION)?,
+        })
+    }
+}
+
+#[inline]
+fn __default_path() -> PathBuf {
+    PathBuf::from("./logs/hazel.log")
+}
@@ -0,0 +1,66 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::{logging::optional_level, util};
+use azalia::config::{
+    env::{self, TryFromEnv},
+    merge::Merge,
+};
+use serde::{Deserialize, Serialize};
+use tracing::Level;
+
+pub const ENABLED: &str = "HAZEL_LOG_JOURNALD";
+pub const LEVEL: &str = "HAZEL_LOG_JOURNALD_LEVEL";
+pub const IDENTIFIER: &str = "HAZEL_LOG_JOURNALD_IDENTIFIER";
+
+/// ## `[logging.journald]` table
+/// Sends logs to the systemd journal over its native protocol, with the fields of
+/// each event as journal fields.
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Most verbose level that is sent to the journal. Defaults to `logging.level`.
+    #[serde(default, with = "optional_level", skip_serializing_if = "Option::is_none")]
+    pub level: Option<Level>,
+
+    /// The `SYSLOG_IDENTIFIER` that entries are tagged with.
+    #[serde(default = "__default_identifier")]
+    pub identifier: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            level: None,
+            identifier: __default_identifier(),
+        }
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            level: env::try_parse_optional(LEVEL)?,
+            identifier: util::env_from_str(IDENTIFIER, __default_identifier())?,
+        })
+    }
+}
+
+#[inline]
+fn __default_identifier() -> String {
+    String::from("hazel")
+}
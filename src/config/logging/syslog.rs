@@ -0,0 +1,188 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::{logging::optional_level, util};
+use azalia::config::{
+    env::{self, TryFromEnv},
+    merge::Merge,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+use tracing::Level;
+use url::Url;
+
+pub const ENABLED: &str = "HAZEL_LOG_SYSLOG";
+pub const ADDRESS: &str = "HAZEL_LOG_SYSLOG_ADDRESS";
+pub const LEVEL: &str = "HAZEL_LOG_SYSLOG_LEVEL";
+pub const JSON: &str = "HAZEL_LOG_SYSLOG_JSON";
+pub const APP_NAME: &str = "HAZEL_LOG_SYSLOG_APP_NAME";
+pub const FACILITY: &str = "HAZEL_LOG_SYSLOG_FACILITY";
+
+/// ## `[logging.syslog]` table
+/// Sends logs to a syslog server as [RFC 5424] messages, over UDP or a Unix datagram
+/// socket.
+///
+/// ```toml
+/// [logging.syslog]
+/// address = "udp://logs.internal:514"
+/// facility = "local0"
+/// ```
+///
+/// [RFC 5424]: https://www.rfc-editor.org/rfc/rfc5424
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Where messages are sent to: either `udp://host:port` (the port defaults to
+    /// `514`), or `unix:///path/to/socket`.
+    #[serde(default = "__default_address")]
+    pub address: Url,
+
+    /// Most verbose level that is sent. Defaults to `logging.level`.
+    #[serde(default, with = "optional_level", skip_serializing_if = "Option::is_none")]
+    pub level: Option<Level>,
+
+    /// whether or not to send the message of each log as a JSON object.
+    #[serde(default)]
+    #[merge(strategy = azalia::config::merge::strategy::bool::only_if_falsy)]
+    pub json: bool,
+
+    /// The `APP-NAME` that messages are tagged with.
+    #[serde(default = "__default_app_name")]
+    pub app_name: String,
+
+    /// Facility that messages are sent as.
+    #[serde(default)]
+    pub facility: Facility,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            address: __default_address(),
+            level: None,
+            json: false,
+            app_name: __default_app_name(),
+            facility: Facility::default(),
+        }
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            address: util::env_from_str(ADDRESS, __default_address())?,
+            level: env::try_parse_optional(LEVEL)?,
+            json: util::bool_env(JSON)?,
+            app_name: util::env_from_str(APP_NAME, __default_app_name())?,
+            facility: util::env_from_str(FACILITY, Facility::default())?,
+        })
+    }
+}
+
+/// Syslog facility that messages are sent as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Facility {
+    User,
+
+    #[default]
+    Daemon,
+
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Facility {
+    const ALL: [Facility; 10] = [
+        Facility::User,
+        Facility::Daemon,
+        Facility::Local0,
+        Facility::Local1,
+        Facility::Local2,
+        Facility::Local3,
+        Facility::Local4,
+        Facility::Local5,
+        Facility::Local6,
+        Facility::Local7,
+    ];
+
+    /// Returns the numerical code of this facility.
+    pub const fn code(self) -> u8 {
+        match self {
+            Facility::User => 1,
+            Facility::Daemon => 3,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+impl FromStr for Facility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim().to_ascii_lowercase();
+        Facility::ALL
+            .into_iter()
+            .find(|facility| facility.to_string() == input)
+            .ok_or_else(|| {
+                format!(
+                    "unknown syslog facility `{input}`: expected `user`, `daemon` or `local0` through `local7`"
+                )
+            })
+    }
+}
+
+impl Display for Facility {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Facility::User => f.write_str("user"),
+            Facility::Daemon => f.write_str("daemon"),
+            local => write!(f, "local{}", local.code() - Facility::Local0.code()),
+        }
+    }
+}
+
+impl Merge for Facility {
+    fn merge(&mut self, other: Self) {
+        if other != Facility::default() {
+            *self = other;
+        }
+    }
+}
+
+#[inline]
+fn __default_address() -> Url {
+    Url::parse("unix:///dev/log").unwrap()
+}
+
+#[inline]
+fn __default_app_name() -> String {
+    String::from("hazel")
+}
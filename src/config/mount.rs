@@ -0,0 +1,87 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::{
+    server::{self, autoindex, compression},
+    storage,
+};
+use azalia::config::merge::Merge;
+use serde::{Deserialize, Serialize};
+
+/// ## `[mounts."<prefix>"]` table
+/// Serves the objects under a path prefix from a storage backend of their own, instead
+/// of the one from the `[storage]` table.
+///
+/// ```toml
+/// [mounts."/releases"]
+/// index_files = ["index.html"]
+///
+/// [mounts."/releases".storage.s3]
+/// bucket = "releases"
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mount {
+    /// The storage backend that objects under the prefix are served from.
+    pub storage: storage::Config,
+
+    /// Whether if the prefix is removed from the path of the request before looking up
+    /// the object, so that `/releases/v1.tar.gz` is `v1.tar.gz` in the storage backend.
+    /// Otherwise, it's looked up as `releases/v1.tar.gz`.
+    #[serde(default = "__default_strip_prefix")]
+    pub strip_prefix: bool,
+
+    /// Overrides `server.index_files` for this mount.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_files: Option<Vec<String>>,
+
+    /// Overrides `server.fallback` for this mount. The fallback document is located in
+    /// this mount's storage backend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+
+    /// Overrides `server.precompressed` for this mount.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precompressed: Option<Vec<compression::Algorithm>>,
+
+    /// Overrides `server.autoindex` for this mount.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoindex: Option<autoindex::Config>,
+}
+
+impl Mount {
+    /// Applies the settings that this mount overrides to `server`.
+    pub fn apply(&self, server: &mut server::Config) {
+        if let Some(ref index_files) = self.index_files {
+            server.index_files = index_files.clone();
+        }
+
+        if let Some(ref fallback) = self.fallback {
+            server.fallback = Some(fallback.clone());
+        }
+
+        if let Some(ref precompressed) = self.precompressed {
+            server.precompressed = precompressed.clone();
+        }
+
+        if let Some(ref autoindex) = self.autoindex {
+            server.autoindex = Some(autoindex.clone());
+        }
+    }
+}
+
+const fn __default_strip_prefix() -> bool {
+    true
+}
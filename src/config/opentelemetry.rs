@@ -27,31 +27,34 @@ pub const URL: &str = "HAZEL_TRACING_OTEL_URL";
 /// Represents the configuration for using an [OpenTelemetry Collector] to report tracing
 /// metadata, in return, can be exported to different software that supports it.
 ///
+/// Spans are exported over gRPC if the URL's scheme is `grpc`, or over HTTP with
+/// protobuf if it is `http` or `https`.
+///
 /// ## Example (gRPC)
 /// ```toml
 /// [tracing.opentelemetry]
-/// url = "grpc://localhost:4318"
+/// url = "grpc://localhost:4317"
 /// ```
 ///
 /// ## Example (HTTP)
-/// ```hcl
-/// tracing "opentelemetry" {
-///   url = "http://localhost:4318"
-/// }
+/// ```toml
+/// [tracing.opentelemetry]
+/// url = "http://localhost:4318"
 /// ```
 ///
 /// [OpenTelemetry Collector]: https://opentelemetry.io/docs/collector
 #[derive(Debug, Clone, Serialize, Deserialize, Merge)]
 pub struct Config {
-    /// list of labels to append when creating the connection to OpenTelemetry collector. `ume` will
-    /// provide the following labels:
+    /// list of labels to append as resource attributes when exporting spans to the
+    /// OpenTelemetry collector. Hazel will provide the following labels:
     ///
     /// * `service.name`
-    /// * `ume.version`
+    /// * `service.version`
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub labels: HashMap<String, String>,
 
-    /// [`Url`][url::Url] used to connect to an available OpenTelemetry collector
+    /// [`Url`][url::Url] used to connect to an available OpenTelemetry collector. For
+    /// HTTP, `/v1/traces` is used as the path if the URL doesn't have one.
     #[serde(default = "__default_url")]
     pub url: Url,
 }
@@ -77,7 +80,7 @@ impl Default for Config {
 }
 
 fn __default_url() -> Url {
-    url::Url::parse("grpc://localhost:4318").expect("a valid url to be parsed")
+    url::Url::parse("grpc://localhost:4317").expect("a valid url to be parsed")
 }
 
 #[cfg(test)]
@@ -13,6 +13,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod access;
+pub mod access_log;
+pub mod autoindex;
+pub mod basic_auth;
+pub mod cache;
+pub mod coalescing;
+pub mod compression;
+pub mod disk_cache;
+pub mod errors;
+pub mod headers;
+pub mod jwt;
+pub mod metrics;
+pub mod rate_limit;
+pub mod request_id;
+pub mod signing;
 pub mod ssl;
 
 use crate::config::util;
@@ -24,6 +39,9 @@ use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, net::SocketAddr};
 
 pub const HEADERS: &str = "HAZEL_SERVER_HEADERS";
+pub const INDEX_FILES: &str = "HAZEL_SERVER_INDEX_FILES";
+pub const FALLBACK: &str = "HAZEL_SERVER_FALLBACK";
+pub const PRECOMPRESSED: &str = "HAZEL_SERVER_PRECOMPRESSED";
 pub const HOST: &[&str; 2] = &["HAZEL_SERVER_HOST", "HOST"];
 pub const PORT: &[&str; 2] = &["HAZEL_SERVER_PORT", "PORT"];
 
@@ -32,10 +50,16 @@ pub const PORT: &[&str; 2] = &["HAZEL_SERVER_PORT", "PORT"];
 #[derive(Debug, Clone, Merge, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
-    /// A list of headers to append to all responses.
+    /// A list of headers to set on all responses.
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub headers: BTreeMap<String, String>,
 
+    /// A list of rules that set or remove headers only on responses whose request path
+    /// and status match them. Rules are applied in order after [`Config::headers`], so
+    /// they can override or remove those and the default headers like `server`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub header_rules: Vec<headers::Rule>,
+
     /// The host to bind towards.
     #[serde(default = "__default_host")]
     pub host: String,
@@ -46,15 +70,125 @@ pub struct Config {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ssl: Option<ssl::Config>,
+
+    /// List of file names that are served when a directory is requested, like
+    /// `index.html`. The first one that exists in the directory is served.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub index_files: Vec<String>,
+
+    /// Path to a file that is served instead of a `404 Not Found`, like the
+    /// `/index.html` of a single page app that handles its routes on the client.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+
+    /// Configures the bodies of error responses.
+    #[serde(default)]
+    pub errors: errors::Config,
+
+    /// Configures the ID that identifies each request.
+    #[serde(default)]
+    pub request_id: request_id::Config,
+
+    /// List of algorithms whose precompressed variants of a file (like `app.js.br` next
+    /// to `app.js`) are served instead of it to clients that accept them, in order of
+    /// preference when the client has none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub precompressed: Vec<compression::Algorithm>,
+
+    /// Compresses responses on the fly. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<compression::Config>,
+
+    /// List of IP addresses or CIDR ranges of reverse proxies in front of Hazel. The
+    /// client's IP address is only taken from the `Forwarded` or `X-Forwarded-For`
+    /// headers (or a PROXY protocol header) of connections from these.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_proxies: Vec<access::Cidr>,
+
+    /// Requires every connection to start with a PROXY protocol (v1 or v2) header, as
+    /// sent by load balancers like HAProxy or AWS' Network Load Balancer.
+    #[serde(default)]
+    pub proxy_protocol: bool,
+
+    /// Ordered list of rules that allow or deny requests based on the client's IP
+    /// address. The first rule that matches decides, and requests that don't match any
+    /// are allowed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access_rules: Vec<access::Rule>,
+
+    /// Rules that limit the rate of requests and bytes, and the amount of concurrent
+    /// requests, per client or prefix. Every rule that applies to a request has to allow
+    /// it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rate_limits: Vec<rate_limit::Rule>,
+
+    /// Realms that require HTTP Basic authentication to access objects in some
+    /// prefixes. If a path is in multiple realms, then the first one applies.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub basic_auth: Vec<basic_auth::Realm>,
+
+    /// Requires bearer tokens to access objects in some prefixes. This is disabled by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwt: Option<jwt::Config>,
+
+    /// Requires signed URLs to access objects in some prefixes. This is disabled by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing: Option<signing::Config>,
+
+    /// Allows listing the contents of directories. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub autoindex: Option<autoindex::Config>,
+
+    /// Exposes Prometheus metrics. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<metrics::Config>,
+
+    /// Writes every request to an access log file. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_log: Option<access_log::Config>,
+
+    /// Caches recently served objects in memory. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<cache::Config>,
+
+    /// Caches objects from S3 and Azure on the local disk. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_cache: Option<disk_cache::Config>,
+
+    /// Deduplicates concurrent calls to the storage backends. This is disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coalescing: Option<coalescing::Config>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             headers: BTreeMap::new(),
+            header_rules: Vec::new(),
             host: __default_host(),
             port: __default_port(),
             ssl: None,
+            index_files: Vec::new(),
+            fallback: None,
+            errors: errors::Config::default(),
+            request_id: request_id::Config::default(),
+            precompressed: Vec::new(),
+            compression: None,
+            trusted_proxies: Vec::new(),
+            proxy_protocol: false,
+            access_rules: Vec::new(),
+            rate_limits: Vec::new(),
+            basic_auth: Vec::new(),
+            jwt: None,
+            signing: None,
+            autoindex: None,
+            metrics: None,
+            access_log: None,
+            cache: None,
+            disk_cache: None,
+            coalescing: None,
         }
     }
 }
@@ -71,6 +205,7 @@ impl TryFromEnv for Config {
     fn try_from_env() -> Result<Self, Self::Error> {
         Ok(Config {
             headers: env::try_parse(HEADERS).unwrap_or_else(|_| Default::default()),
+            header_rules: Vec::new(),
             host: env::try_parse_or_else(HOST[0], env::try_parse_or_else(HOST[1], __default_host())?)?,
             port: env::try_parse_or_else(PORT[0], env::try_parse_or_else(PORT[1], __default_port())?)?,
             ssl: match util::bool_env(ssl::ENABLED) {
@@ -78,6 +213,61 @@ impl TryFromEnv for Config {
                 Ok(false) => None,
                 Err(e) => return Err(e),
             },
+            index_files: util::list_env(INDEX_FILES).unwrap_or_default(),
+            fallback: env::try_parse(FALLBACK).ok(),
+            errors: errors::Config::try_from_env()?,
+            request_id: request_id::Config::try_from_env()?,
+            precompressed: compression::parse_algorithms(PRECOMPRESSED)?.unwrap_or_default(),
+            compression: match util::bool_env(compression::ENABLED) {
+                Ok(true) => compression::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            trusted_proxies: access::parse_cidrs(access::TRUSTED_PROXIES)?,
+            proxy_protocol: util::bool_env(access::PROXY_PROTOCOL)?,
+            access_rules: Vec::new(),
+            rate_limits: Vec::new(),
+            basic_auth: basic_auth::realms_from_env()?,
+            jwt: match util::bool_env(jwt::ENABLED) {
+                Ok(true) => jwt::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            signing: match util::bool_env(signing::ENABLED) {
+                Ok(true) => signing::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            autoindex: match util::bool_env(autoindex::ENABLED) {
+                Ok(true) => autoindex::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            metrics: match util::bool_env(metrics::ENABLED) {
+                Ok(true) => metrics::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            access_log: match util::bool_env(access_log::ENABLED) {
+                Ok(true) => access_log::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            cache: match util::bool_env(cache::ENABLED) {
+                Ok(true) => cache::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            disk_cache: match util::bool_env(disk_cache::ENABLED) {
+                Ok(true) => disk_cache::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+            coalescing: match util::bool_env(coalescing::ENABLED) {
+                Ok(true) => coalescing::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
         })
     }
 }
@@ -0,0 +1,186 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::merge::Merge;
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+use std::{fmt::Display, net::IpAddr, str::FromStr};
+
+pub const TRUSTED_PROXIES: &str = "HAZEL_SERVER_TRUSTED_PROXIES";
+pub const PROXY_PROTOCOL: &str = "HAZEL_SERVER_PROXY_PROTOCOL";
+
+/// ## `[[server.access_rules]]` array of tables
+/// Allows or denies requests based on the client's IP address. Rules are evaluated
+/// in order and the first one that matches decides; requests that don't match any
+/// rule are allowed.
+///
+/// ```toml
+/// [[server.access_rules]]
+/// action = "allow"
+/// ips = ["10.0.0.0/8"]
+/// prefixes = ["/internal"]
+///
+/// [[server.access_rules]]
+/// action = "deny"
+/// prefixes = ["/internal"]
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Whether requests that match this rule are allowed or denied.
+    pub action: Action,
+
+    /// List of IP addresses or CIDR ranges that the client's IP address needs to be
+    /// in one of. If empty, then this rule applies to all addresses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ips: Vec<Cidr>,
+
+    /// List of path prefixes that the request's path needs to be in one of. If empty,
+    /// then this rule applies to all paths.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefixes: Vec<String>,
+}
+
+impl Rule {
+    /// Checks if this rule applies to a request from `ip` to `path`.
+    pub fn matches(&self, ip: IpAddr, path: &str) -> bool {
+        (self.ips.is_empty() || self.ips.iter().any(|cidr| cidr.contains(ip))) &&
+            (self.prefixes.is_empty() || self.prefixes.iter().any(|prefix| util::has_path_prefix(path, prefix)))
+    }
+
+    /// Rejects prefixes that don't start with a `/`, as they would never match.
+    pub fn validate(&self) -> eyre::Result<()> {
+        util::check_prefixes("server.access_rules.prefixes", &self.prefixes)
+    }
+}
+
+/// Checks if a request from `ip` to `path` is allowed by `rules`.
+pub fn is_allowed(rules: &[Rule], ip: IpAddr, path: &str) -> bool {
+    rules
+        .iter()
+        .find(|rule| rule.matches(ip, path))
+        .is_none_or(|rule| rule.action == Action::Allow)
+}
+
+/// What to do with requests that match a [`Rule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Allow,
+    Deny,
+}
+
+impl Merge for Action {
+    fn merge(&mut self, other: Self) {
+        *self = other;
+    }
+}
+
+/// An IP address or a CIDR range, like `192.0.2.1` or `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr(pub IpNet);
+
+impl Cidr {
+    /// Checks if `ip` is in this range. IPv4-mapped IPv6 addresses are compared as
+    /// their IPv4 address.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.contains(&ip.to_canonical())
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = ipnet::AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<IpAddr>() {
+            Ok(ip) => Ok(Cidr(IpNet::from(ip))),
+            Err(_) => s.parse().map(Cidr),
+        }
+    }
+}
+
+impl Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Serialize for Cidr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cidr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|_| D::Error::custom(format!("invalid ip address or cidr range `{value}`")))
+    }
+}
+
+/// Parses a comma-separated list of [`Cidr`]s from the environment variable `key`.
+pub(crate) fn parse_cidrs(key: &str) -> eyre::Result<Vec<Cidr>> {
+    util::list_env(key)
+        .unwrap_or_default()
+        .iter()
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| eyre::eyre!("`${key}`: invalid ip address or cidr range `{value}`"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_allowed() {
+        let rules: Vec<Rule> = toml::from_str::<toml::Table>(
+            r#"
+            [[rules]]
+            action = "allow"
+            ips = ["10.0.0.0/8", "192.0.2.1"]
+            prefixes = ["/internal"]
+
+            [[rules]]
+            action = "deny"
+            prefixes = ["/internal"]
+
+            [[rules]]
+            action = "deny"
+            ips = ["203.0.113.0/24"]
+            "#,
+        )
+        .unwrap()["rules"]
+            .clone()
+            .try_into()
+            .unwrap();
+
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        assert!(is_allowed(&rules, ip("10.1.2.3"), "/internal/a.txt"));
+        assert!(is_allowed(&rules, ip("::ffff:10.1.2.3"), "/internal/a.txt"));
+        assert!(is_allowed(&rules, ip("192.0.2.1"), "/internal"));
+        assert!(!is_allowed(&rules, ip("192.0.2.2"), "/internal/a.txt"));
+        assert!(is_allowed(&rules, ip("192.0.2.2"), "/internals/a.txt"));
+        assert!(!is_allowed(&rules, ip("203.0.113.9"), "/a.txt"));
+        assert!(is_allowed(&rules, ip("2001:db8::1"), "/a.txt"));
+    }
+}
@@ -0,0 +1,138 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::{logging::Rotation, util};
+use azalia::config::{env::TryFromEnv, merge::Merge};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, path::PathBuf, str::FromStr};
+
+pub const ENABLED: &str = "HAZEL_SERVER_ACCESS_LOG";
+pub const PATH: &str = "HAZEL_SERVER_ACCESS_LOG_PATH";
+pub const FORMAT: &str = "HAZEL_SERVER_ACCESS_LOG_FORMAT";
+pub const ROTATION: &str = "HAZEL_SERVER_ACCESS_LOG_ROTATION";
+
+/// ## `[server.access_log]` table
+/// Writes a line for every request to a dedicated access log file, separately from
+/// Hazel's own logs.
+///
+/// ```toml
+/// [server.access_log]
+/// path = "/var/log/hazel/access.log"
+/// format = "combined"
+///
+/// [server.access_log.rotation]
+/// max_size = 104857600
+/// interval = "daily"
+/// max_files = 7
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Path to the file that the access log is written to. Its directory is created
+    /// if it doesn't exist.
+    #[serde(default = "__default_path")]
+    pub path: PathBuf,
+
+    /// Format of each line.
+    #[serde(default)]
+    pub format: Format,
+
+    /// Configures when the file is rotated.
+    #[serde(default)]
+    pub rotation: Rotation,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            path: __default_path(),
+            format: Format::default(),
+            rotation: Rotation::default(),
+        }
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            path: util::env_from_str(PATH, __default_path())?,
+            format: util::env_from_str(FORMAT, Format::default())?,
+            rotation: Rotation::from_env(ROTATION)?,
+        })
+    }
+}
+
+/// Format of the lines in the access log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// The NCSA Common Log Format.
+    Common,
+
+    /// Apache's Combined Log Format, which adds the referer and user agent to the
+    /// Common Log Format. Hazel appends the request ID and the latency in milliseconds
+    /// to each line.
+    #[default]
+    Combined,
+
+    /// The W3C Extended Log File Format, with a `#Fields` directive at the start of
+    /// each file.
+    W3c,
+
+    /// A JSON object on each line.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.trim().to_ascii_lowercase() {
+            "common" => Ok(Format::Common),
+            "combined" => Ok(Format::Combined),
+            "w3c" => Ok(Format::W3c),
+            "json" => Ok(Format::Json),
+            input => Err(format!(
+                "unknown access log format `{input}`: expected `common`, `combined`, `w3c` or `json`"
+            )),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Common => f.write_str("common"),
+            Format::Combined => f.write_str("combined"),
+            Format::W3c => f.write_str("w3c"),
+            Format::Json => f.write_str("json"),
+        }
+    }
+}
+
+impl Merge for Format {
+    fn merge(&mut self, other: Self) {
+        if other != Format::default() {
+            *self = other;
+        }
+    }
+}
+
+#[inline]
+fn __default_path() -> PathBuf {
+    PathBuf::from("./logs/access.log")
+}
@@ -0,0 +1,95 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{env::TryFromEnv, merge::Merge};
+use serde::{Deserialize, Serialize};
+
+pub const ENABLED: &str = "HAZEL_SERVER_AUTOINDEX";
+pub const PREFIXES: &str = "HAZEL_SERVER_AUTOINDEX_PREFIXES";
+pub const PAGE_SIZE: &str = "HAZEL_SERVER_AUTOINDEX_PAGE_SIZE";
+
+/// ## `[server.autoindex]` table
+/// Renders the contents of directories as a browsable HTML page, or as JSON if the
+/// client asks for it with `Accept: application/json`.
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// List of path prefixes that directories can be listed in, like `/releases`. A
+    /// prefix of `/` allows listing every directory.
+    #[serde(default = "__default_prefixes")]
+    pub prefixes: Vec<String>,
+
+    /// Maximum amount of entries to show on each page. S3 won't return more than
+    /// 1,000 objects at once.
+    #[serde(default = "__default_page_size")]
+    pub page_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            prefixes: __default_prefixes(),
+            page_size: __default_page_size(),
+        }
+    }
+}
+
+impl Config {
+    /// Checks if the directory at `path` can be listed.
+    pub fn is_enabled_for(&self, path: &str) -> bool {
+        self.prefixes.iter().any(|prefix| util::has_path_prefix(path, prefix))
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            prefixes: util::list_env(PREFIXES).unwrap_or_else(__default_prefixes),
+            page_size: util::env_from_str(PAGE_SIZE, __default_page_size())?,
+        })
+    }
+}
+
+#[inline]
+fn __default_prefixes() -> Vec<String> {
+    vec![String::from("/")]
+}
+
+const fn __default_page_size() -> usize {
+    1000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn test_is_enabled_for() {
+        let config = Config {
+            prefixes: vec![String::from("/releases/")],
+            page_size: 1000,
+        };
+
+        assert!(config.is_enabled_for("/releases"));
+        assert!(config.is_enabled_for("/releases/v1/"));
+        assert!(!config.is_enabled_for("/releases-old/"));
+        assert!(!config.is_enabled_for("/"));
+
+        assert!(Config::default().is_enabled_for("/anything/"));
+    }
+}
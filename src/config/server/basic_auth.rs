@@ -0,0 +1,87 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{env, merge::Merge};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub const HTPASSWD: &str = "HAZEL_SERVER_BASIC_AUTH_HTPASSWD";
+pub const REALM: &str = "HAZEL_SERVER_BASIC_AUTH_REALM";
+pub const PREFIXES: &str = "HAZEL_SERVER_BASIC_AUTH_PREFIXES";
+
+/// ## `[[server.basic_auth]]` array of tables
+/// Requires requests in some prefixes to authenticate with HTTP Basic authentication,
+/// against the users in an htpasswd file.
+///
+/// ```toml
+/// [[server.basic_auth]]
+/// realm = "internal mirror"
+/// prefixes = ["/internal"]
+/// htpasswd = "/etc/hazel/htpasswd"
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Realm {
+    /// Name of the realm that is sent in the `WWW-Authenticate` challenge.
+    #[serde(default = "__default_realm")]
+    pub realm: String,
+
+    /// List of path prefixes that are protected by this realm. A prefix of `/` protects
+    /// every object.
+    #[serde(default = "__default_prefixes")]
+    pub prefixes: Vec<String>,
+
+    /// Location to an htpasswd file with `user:hash` lines. Hashes can be bcrypt
+    /// (`$2y$`, as created by `htpasswd -B`) or Argon2 (`$argon2id$`). The file is
+    /// reloaded when it changes.
+    pub htpasswd: PathBuf,
+}
+
+impl Realm {
+    /// Checks if requests to `path` are protected by this realm.
+    pub fn is_required_for(&self, path: &str) -> bool {
+        self.prefixes.iter().any(|prefix| util::has_path_prefix(path, prefix))
+    }
+
+    /// Rejects prefixes that don't start with a `/`, as they would never match.
+    pub fn validate(&self) -> eyre::Result<()> {
+        util::check_prefixes("server.basic_auth.prefixes", &self.prefixes)
+    }
+}
+
+/// Loads a single realm from the `HAZEL_SERVER_BASIC_AUTH_*` environment variables,
+/// if `HAZEL_SERVER_BASIC_AUTH_HTPASSWD` is set.
+pub(crate) fn realms_from_env() -> eyre::Result<Vec<Realm>> {
+    let Some(htpasswd) = env::try_parse_optional(HTPASSWD)? else {
+        return Ok(Vec::new());
+    };
+
+    Ok(vec![Realm {
+        realm: env::try_parse_or_else(REALM, __default_realm())?,
+        prefixes: util::list_env(PREFIXES).unwrap_or_else(__default_prefixes),
+        htpasswd,
+    }])
+}
+
+#[inline]
+fn __default_realm() -> String {
+    String::from("hazel")
+}
+
+#[inline]
+fn __default_prefixes() -> Vec<String> {
+    vec![String::from("/")]
+}
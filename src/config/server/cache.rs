@@ -0,0 +1,86 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{env::TryFromEnv, merge::Merge};
+use serde::{Deserialize, Serialize};
+
+pub const ENABLED: &str = "HAZEL_SERVER_CACHE";
+pub const MAX_SIZE: &str = "HAZEL_SERVER_CACHE_MAX_SIZE";
+pub const MAX_OBJECT_SIZE: &str = "HAZEL_SERVER_CACHE_MAX_OBJECT_SIZE";
+pub const TTL: &str = "HAZEL_SERVER_CACHE_TTL";
+
+/// ## `[server.cache]` table
+/// Keeps recently served objects in memory, so that hot objects don't round-trip to
+/// the storage backend on every request.
+///
+/// ```toml
+/// [server.cache]
+/// max_size = 268435456
+/// max_object_size = 8388608
+/// ttl = 60
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Most amount of bytes that are held in the cache. The least recently used objects
+    /// are evicted once it's full.
+    #[serde(default = "__default_max_size")]
+    pub max_size: u64,
+
+    /// Objects larger than this many bytes are never cached.
+    #[serde(default = "__default_max_object_size")]
+    pub max_object_size: u64,
+
+    /// Amount of seconds that a cached object is served without asking the storage
+    /// backend. Afterwards, it is revalidated against the backend's entity tag and only
+    /// read again if it changed.
+    #[serde(default = "__default_ttl")]
+    pub ttl: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_size: __default_max_size(),
+            max_object_size: __default_max_object_size(),
+            ttl: __default_ttl(),
+        }
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            max_size: util::env_from_str(MAX_SIZE, __default_max_size())?,
+            max_object_size: util::env_from_str(MAX_OBJECT_SIZE, __default_max_object_size())?,
+            ttl: util::env_from_str(TTL, __default_ttl())?,
+        })
+    }
+}
+
+const fn __default_max_size() -> u64 {
+    256 * 1024 * 1024
+}
+
+const fn __default_max_object_size() -> u64 {
+    8 * 1024 * 1024
+}
+
+const fn __default_ttl() -> u64 {
+    60
+}
@@ -0,0 +1,75 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{env::TryFromEnv, merge::Merge};
+use serde::{Deserialize, Serialize};
+
+pub const ENABLED: &str = "HAZEL_SERVER_COALESCING";
+pub const MAX_OBJECT_SIZE: &str = "HAZEL_SERVER_COALESCING_MAX_OBJECT_SIZE";
+pub const MAX_BUFFERED_SIZE: &str = "HAZEL_SERVER_COALESCING_MAX_BUFFERED_SIZE";
+
+/// ## `[server.coalescing]` table
+/// Deduplicates concurrent requests for the same object, so that they share one call to
+/// the storage backend instead of each making their own.
+///
+/// ```toml
+/// [server.coalescing]
+/// max_object_size = 67108864
+/// max_buffered_size = 268435456
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Contents of objects up to this many bytes are read once for every request that
+    /// wants them at the same time. Larger objects are read by each request on its own,
+    /// since the shared contents are held in memory until every request has sent them.
+    #[serde(default = "__default_max_object_size")]
+    pub max_object_size: u64,
+
+    /// Contents of objects are only read once for every request that wants them while
+    /// the shared reads hold up to this many bytes in memory altogether. Past that,
+    /// objects are read by each request on its own.
+    #[serde(default = "__default_max_buffered_size")]
+    pub max_buffered_size: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_object_size: __default_max_object_size(),
+            max_buffered_size: __default_max_buffered_size(),
+        }
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            max_object_size: util::env_from_str(MAX_OBJECT_SIZE, __default_max_object_size())?,
+            max_buffered_size: util::env_from_str(MAX_BUFFERED_SIZE, __default_max_buffered_size())?,
+        })
+    }
+}
+
+const fn __default_max_object_size() -> u64 {
+    64 * 1024 * 1024
+}
+
+const fn __default_max_buffered_size() -> u64 {
+    256 * 1024 * 1024
+}
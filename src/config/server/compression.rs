@@ -0,0 +1,284 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{env::TryFromEnv, merge::Merge};
+use eyre::eyre;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt::Display, str::FromStr};
+
+pub const ENABLED: &str = "HAZEL_SERVER_COMPRESSION";
+pub const ALGORITHMS: &str = "HAZEL_SERVER_COMPRESSION_ALGORITHMS";
+pub const CONTENT_TYPES: &str = "HAZEL_SERVER_COMPRESSION_CONTENT_TYPES";
+pub const MIN_SIZE: &str = "HAZEL_SERVER_COMPRESSION_MIN_SIZE";
+pub const LEVEL: &str = "HAZEL_SERVER_COMPRESSION_LEVEL";
+
+/// ## `[server.compression]` table
+/// Compresses responses on the fly with an algorithm that the client accepts from its
+/// `Accept-Encoding` header.
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Algorithms that can be used. If the client accepts more than one, its preference
+    /// decides which one is used.
+    #[serde(default = "__default_algorithms")]
+    pub algorithms: Vec<Algorithm>,
+
+    /// List of content types that are compressed. A content type can end with `/*`
+    /// to match any subtype, like `text/*`.
+    #[serde(default = "__default_content_types")]
+    pub content_types: Vec<String>,
+
+    /// Responses smaller than this many bytes aren't compressed, as the overhead of
+    /// compressing them outweighs the savings.
+    #[serde(default = "__default_min_size")]
+    pub min_size: u64,
+
+    /// Compression level to use: `fastest`, `default`, `best`, or a number that is
+    /// specific to the algorithm.
+    #[serde(default)]
+    pub level: Level,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            algorithms: __default_algorithms(),
+            content_types: __default_content_types(),
+            min_size: __default_min_size(),
+            level: Level::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Checks if a response with the given `Content-Type` should be compressed.
+    pub fn is_compressible(&self, content_type: &str) -> bool {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        self.content_types
+            .iter()
+            .any(|allowed| match allowed.strip_suffix("/*") {
+                Some(ty) => essence.split_once('/').is_some_and(|(essence_ty, _)| essence_ty == ty),
+                None => essence == allowed.to_ascii_lowercase(),
+            })
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            algorithms: parse_algorithms(ALGORITHMS)?.unwrap_or_else(__default_algorithms),
+            content_types: util::list_env(CONTENT_TYPES).unwrap_or_else(__default_content_types),
+            min_size: util::env_from_str(MIN_SIZE, __default_min_size())?,
+            level: util::env_from_str(LEVEL, Level::default())?,
+        })
+    }
+}
+
+/// A compression algorithm that can be negotiated with `Accept-Encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Algorithm {
+    #[serde(rename = "gzip")]
+    Gzip,
+
+    #[serde(rename = "br", alias = "brotli")]
+    Brotli,
+
+    #[serde(rename = "zstd")]
+    Zstd,
+}
+
+impl Algorithm {
+    /// Returns the content coding of this algorithm, as used in `Accept-Encoding` and
+    /// `Content-Encoding`.
+    pub const fn coding(&self) -> &'static str {
+        match self {
+            Algorithm::Gzip => "gzip",
+            Algorithm::Brotli => "br",
+            Algorithm::Zstd => "zstd",
+        }
+    }
+
+    /// Returns the file extension of files that were compressed with this algorithm.
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Algorithm::Gzip => "gz",
+            Algorithm::Brotli => "br",
+            Algorithm::Zstd => "zst",
+        }
+    }
+}
+
+/// Parses a comma-separated list of algorithms from the environment variable `key`,
+/// or returns `None` if it isn't set.
+pub(crate) fn parse_algorithms(key: &str) -> eyre::Result<Option<Vec<Algorithm>>> {
+    let Some(algorithms) = util::list_env(key) else {
+        return Ok(None);
+    };
+
+    algorithms
+        .iter()
+        .map(|algorithm| algorithm.parse())
+        .collect::<Result<_, _>>()
+        .map(Some)
+        .map_err(|e| eyre!("failed to parse environment variable `${}`: {}", key, e))
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.trim().to_ascii_lowercase() {
+            "gzip" => Ok(Algorithm::Gzip),
+            "br" | "brotli" => Ok(Algorithm::Brotli),
+            "zstd" => Ok(Algorithm::Zstd),
+            input => Err(format!(
+                "unknown compression algorithm `{input}`: expected `gzip`, `br` or `zstd`"
+            )),
+        }
+    }
+}
+
+/// Level of compression to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Level {
+    Fastest,
+
+    #[default]
+    Default,
+
+    Best,
+
+    /// A level that is specific to the algorithm, like `1..=9` for gzip. Levels that
+    /// are out of range for the algorithm are clamped to it.
+    Precise(i32),
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.trim().to_ascii_lowercase() {
+            "fastest" => Ok(Level::Fastest),
+            "default" | "" => Ok(Level::Default),
+            "best" => Ok(Level::Best),
+            input => input.parse().map(Level::Precise).map_err(|_| {
+                format!("invalid compression level `{input}`: expected `fastest`, `default`, `best` or a number")
+            }),
+        }
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Level::Fastest => f.write_str("fastest"),
+            Level::Default => f.write_str("default"),
+            Level::Best => f.write_str("best"),
+            Level::Precise(level) => write!(f, "{level}"),
+        }
+    }
+}
+
+impl Serialize for Level {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Level::Precise(level) => serializer.serialize_i32(*level),
+            level => serializer.collect_str(level),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Level {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(i32),
+            Named(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(level) => Ok(Level::Precise(level)),
+            Repr::Named(level) => level.parse().map_err(D::Error::custom),
+        }
+    }
+}
+
+impl Merge for Level {
+    fn merge(&mut self, other: Self) {
+        if other != Level::Default {
+            *self = other;
+        }
+    }
+}
+
+#[inline]
+fn __default_algorithms() -> Vec<Algorithm> {
+    vec![Algorithm::Zstd, Algorithm::Brotli, Algorithm::Gzip]
+}
+
+fn __default_content_types() -> Vec<String> {
+    [
+        "text/*",
+        "application/javascript",
+        "application/json",
+        "application/manifest+json",
+        "application/wasm",
+        "application/xml",
+        "image/svg+xml",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+const fn __default_min_size() -> u64 {
+    1024
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Level};
+
+    #[test]
+    fn test_is_compressible() {
+        let config = Config::default();
+        assert!(config.is_compressible("text/css; charset=utf-8"));
+        assert!(config.is_compressible("Application/JSON"));
+        assert!(!config.is_compressible("image/png"));
+        assert!(!config.is_compressible("application/json-seq"));
+    }
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!("best".parse::<Level>(), Ok(Level::Best));
+        assert_eq!("6".parse::<Level>(), Ok(Level::Precise(6)));
+        assert!("fast".parse::<Level>().is_err());
+    }
+}
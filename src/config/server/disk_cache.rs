@@ -0,0 +1,87 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{env::TryFromEnv, merge::Merge};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub const ENABLED: &str = "HAZEL_SERVER_DISK_CACHE";
+pub const DIRECTORY: &str = "HAZEL_SERVER_DISK_CACHE_DIRECTORY";
+pub const MAX_SIZE: &str = "HAZEL_SERVER_DISK_CACHE_MAX_SIZE";
+pub const MAX_OBJECT_SIZE: &str = "HAZEL_SERVER_DISK_CACHE_MAX_OBJECT_SIZE";
+
+/// ## `[server.disk_cache]` table
+/// Keeps objects from S3 and Azure in a directory on the local disk, so that large
+/// objects that don't fit in `[server.cache]` aren't fetched from the storage backend on
+/// every request. The cached objects are kept across restarts.
+///
+/// ```toml
+/// [server.disk_cache]
+/// directory = "/var/cache/hazel"
+/// max_size = 10737418240
+/// max_object_size = 1073741824
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Directory that the objects are cached in. It is created if it doesn't exist, and
+    /// shouldn't be shared with anything else.
+    #[serde(default = "__default_directory")]
+    pub directory: PathBuf,
+
+    /// Most amount of bytes that are held in the directory. The least recently used
+    /// objects are evicted once it's full.
+    #[serde(default = "__default_max_size")]
+    pub max_size: u64,
+
+    /// Objects larger than this many bytes are never cached.
+    #[serde(default = "__default_max_object_size")]
+    pub max_object_size: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            directory: __default_directory(),
+            max_size: __default_max_size(),
+            max_object_size: __default_max_object_size(),
+        }
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            directory: util::env_from_str(DIRECTORY, __default_directory())?,
+            max_size: util::env_from_str(MAX_SIZE, __default_max_size())?,
+            max_object_size: util::env_from_str(MAX_OBJECT_SIZE, __default_max_object_size())?,
+        })
+    }
+}
+
+fn __default_directory() -> PathBuf {
+    PathBuf::from("./cache")
+}
+
+const fn __default_max_size() -> u64 {
+    10 * 1024 * 1024 * 1024
+}
+
+const fn __default_max_object_size() -> u64 {
+    1024 * 1024 * 1024
+}
@@ -0,0 +1,96 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{
+    env::{self, TryFromEnv},
+    merge::Merge,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const PAGES: &str = "HAZEL_SERVER_ERRORS_PAGES";
+pub const PROBLEM_JSON: &str = "HAZEL_SERVER_ERRORS_PROBLEM_JSON";
+
+/// Prefix of an error page's location that reads it from the local filesystem rather
+/// than from the storage backend.
+pub const FILE_SCHEME: &str = "file://";
+
+/// ## `[server.errors]` table
+/// Configures the bodies of error responses.
+///
+/// Clients that prefer HTML (like browsers) get an HTML page, while everything else gets
+/// the JSON envelope, or an RFC 9457 `application/problem+json` body if they ask for it
+/// or [`Config::problem_json`] is enabled.
+#[derive(Debug, Clone, Default, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Documents that are sent to clients that prefer HTML, keyed by a status code
+    /// (`404`), a status class (`4xx`), or `error` for any error.
+    ///
+    /// Each document is located in the storage backend, or on the local filesystem
+    /// if it starts with `file://`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pages: BTreeMap<String, String>,
+
+    /// Whether if `application/problem+json` bodies are sent instead of the JSON envelope
+    /// to clients that accept any JSON but didn't explicitly ask for `application/json`.
+    #[serde(default)]
+    pub problem_json: bool,
+}
+
+impl Config {
+    /// Returns the location of the error document for `status`, preferring the most
+    /// specific one that was configured.
+    pub fn page_for(&self, status: u16) -> Option<&str> {
+        self.pages
+            .get(&status.to_string())
+            .or_else(|| self.pages.get(&format!("{}xx", status / 100)))
+            .or_else(|| self.pages.get("error"))
+            .map(String::as_str)
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            pages: env::try_parse(PAGES).unwrap_or_else(|_| Default::default()),
+            problem_json: util::bool_env(PROBLEM_JSON)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn test_page_for() {
+        let config = Config {
+            pages: [("404", "/errors/404.html"), ("5xx", "file:///etc/hazel/5xx.html")]
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+
+            problem_json: false,
+        };
+
+        assert_eq!(config.page_for(404), Some("/errors/404.html"));
+        assert_eq!(config.page_for(503), Some("file:///etc/hazel/5xx.html"));
+        assert_eq!(config.page_for(403), None);
+    }
+}
@@ -0,0 +1,209 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::http::StatusCode;
+use azalia::config::merge::Merge;
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+/// ## `[[server.header_rules]]` table
+/// A rule that sets or removes headers on responses that match it.
+///
+/// ## Example
+/// ```toml
+/// [[server.header_rules]]
+/// paths = ["/assets/**"]
+/// status = ["2xx"]
+/// set = { "cache-control" = "public, max-age=31536000, immutable" }
+///
+/// [[server.header_rules]]
+/// remove = ["server"]
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// List of path globs that the request's path needs to match one of. `*` matches
+    /// within a single path segment while `**` matches across them. If empty, then this
+    /// rule applies to all paths.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathGlob>,
+
+    /// List of status codes or classes (i.e, `2xx`, `404`, or `error` for both `4xx`
+    /// and `5xx`) that the response's status needs to match one of. If empty, then this
+    /// rule applies to all responses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status: Vec<StatusMatcher>,
+
+    /// Headers to set on the response, overriding them if they were already present.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub set: BTreeMap<String, String>,
+
+    /// Headers to remove from the response, like the default `server` header. This is
+    /// done before the headers in [`Rule::set`] are applied.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove: Vec<String>,
+}
+
+impl Rule {
+    /// Checks if this rule applies to a response with `status` for a request to `path`.
+    pub fn matches(&self, path: &str, status: StatusCode) -> bool {
+        (self.paths.is_empty() || self.paths.iter().any(|glob| glob.is_match(path))) &&
+            (self.status.is_empty() || self.status.iter().any(|matcher| matcher.is_match(status)))
+    }
+}
+
+/// A glob that matches against the path of a request.
+#[derive(Debug, Clone)]
+pub struct PathGlob(GlobMatcher);
+
+impl PathGlob {
+    /// Checks if `path` matches this glob.
+    pub fn is_match(&self, path: &str) -> bool {
+        self.0.is_match(path)
+    }
+}
+
+impl FromStr for PathGlob {
+    type Err = globset::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        GlobBuilder::new(s)
+            .literal_separator(true)
+            .build()
+            .map(|glob| PathGlob(glob.compile_matcher()))
+    }
+}
+
+impl Serialize for PathGlob {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.glob().glob())
+    }
+}
+
+impl<'de> Deserialize<'de> for PathGlob {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Matches the status code of a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusMatcher {
+    /// Matches a single status code, like `404`.
+    Exact(u16),
+
+    /// Matches a class of status codes, like `2xx`.
+    Class(u16),
+
+    /// Matches both client (`4xx`) and server (`5xx`) errors.
+    Error,
+}
+
+impl StatusMatcher {
+    /// Checks if `status` matches.
+    pub fn is_match(&self, status: StatusCode) -> bool {
+        match *self {
+            StatusMatcher::Exact(code) => status.as_u16() == code,
+            StatusMatcher::Class(class) => status.as_u16() / 100 == class,
+            StatusMatcher::Error => status.is_client_error() || status.is_server_error(),
+        }
+    }
+}
+
+impl FromStr for StatusMatcher {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        match s.as_str() {
+            "error" | "errors" => Ok(StatusMatcher::Error),
+            "1xx" | "2xx" | "3xx" | "4xx" | "5xx" => Ok(StatusMatcher::Class(u16::from(s.as_bytes()[0] - b'0'))),
+            code => match code.parse::<u16>() {
+                Ok(code @ 100..=599) => Ok(StatusMatcher::Exact(code)),
+                _ => Err(format!(
+                    "invalid status matcher `{s}`: expected a status code, a class like `2xx`, or `error`"
+                )),
+            },
+        }
+    }
+}
+
+impl Display for StatusMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatusMatcher::Exact(code) => write!(f, "{code}"),
+            StatusMatcher::Class(class) => write!(f, "{class}xx"),
+            StatusMatcher::Error => f.write_str("error"),
+        }
+    }
+}
+
+impl Serialize for StatusMatcher {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for StatusMatcher {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PathGlob, StatusMatcher};
+    use axum::http::StatusCode;
+
+    #[test]
+    fn test_path_globs() {
+        let glob = "/assets/**".parse::<PathGlob>().unwrap();
+        assert!(glob.is_match("/assets/js/app.js"));
+        assert!(!glob.is_match("/index.html"));
+
+        let glob = "/*.html".parse::<PathGlob>().unwrap();
+        assert!(glob.is_match("/index.html"));
+        assert!(!glob.is_match("/docs/index.html"));
+    }
+
+    #[test]
+    fn test_status_matchers() {
+        assert!(
+            "2xx"
+                .parse::<StatusMatcher>()
+                .unwrap()
+                .is_match(StatusCode::PARTIAL_CONTENT)
+        );
+        assert!(
+            "error"
+                .parse::<StatusMatcher>()
+                .unwrap()
+                .is_match(StatusCode::NOT_FOUND)
+        );
+        assert!(!"404".parse::<StatusMatcher>().unwrap().is_match(StatusCode::OK));
+        assert!("6xx".parse::<StatusMatcher>().is_err());
+    }
+}
@@ -0,0 +1,176 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{
+    env::{self, TryFromEnv},
+    merge::Merge,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub const ENABLED: &str = "HAZEL_SERVER_JWT";
+pub const SECRET: &str = "HAZEL_SERVER_JWT_SECRET";
+pub const JWKS: &str = "HAZEL_SERVER_JWT_JWKS";
+pub const AUDIENCE: &str = "HAZEL_SERVER_JWT_AUDIENCE";
+pub const PREFIXES: &str = "HAZEL_SERVER_JWT_PREFIXES";
+pub const CLAIM: &str = "HAZEL_SERVER_JWT_CLAIM";
+pub const LEEWAY: &str = "HAZEL_SERVER_JWT_LEEWAY";
+
+/// ## `[server.jwt]` table
+/// Requires requests in some prefixes to have a bearer token (a JWT) that grants
+/// access to the requested path.
+///
+/// ```toml
+/// [server.jwt]
+/// prefixes = ["/private"]
+/// audience = ["hazel"]
+/// jwks = "/etc/hazel/jwks.json"
+///
+/// [[server.jwt.keys]]
+/// id = "2025-01"
+/// algorithm = "HS256"
+/// secret = "..."
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Keys that tokens can be signed with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<Key>,
+
+    /// Location to a JSON Web Key Set (JWKS) with more keys that tokens can be signed
+    /// with. It is only read when the server starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks: Option<PathBuf>,
+
+    /// List of audiences that the token's `aud` claim needs to contain one of. If
+    /// empty, then the `aud` claim isn't checked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audience: Vec<String>,
+
+    /// List of path prefixes where requests need a token. A prefix of `/` requires it
+    /// for every object.
+    #[serde(default = "__default_prefixes")]
+    pub prefixes: Vec<String>,
+
+    /// Name of the claim with the path prefix (or list of them) that the token grants
+    /// access to.
+    #[serde(default = "__default_claim")]
+    pub claim: String,
+
+    /// Amount of seconds of clock skew to allow when checking the `exp` and `nbf` claims.
+    #[serde(default = "__default_leeway")]
+    pub leeway: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keys: Vec::new(),
+            jwks: None,
+            audience: Vec::new(),
+            prefixes: __default_prefixes(),
+            claim: __default_claim(),
+            leeway: __default_leeway(),
+        }
+    }
+}
+
+impl Config {
+    /// Checks if requests to `path` need a token.
+    pub fn is_required_for(&self, path: &str) -> bool {
+        self.prefixes.iter().any(|prefix| util::has_path_prefix(path, prefix))
+    }
+
+    /// Rejects prefixes that don't start with a `/`, as they would never match.
+    pub fn validate(&self) -> eyre::Result<()> {
+        util::check_prefixes("server.jwt.prefixes", &self.prefixes)
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            keys: match env::try_parse::<_, String>(SECRET) {
+                Ok(secret) => vec![Key {
+                    id: None,
+                    algorithm: Algorithm::HS256,
+                    secret: Some(secret),
+                    public_key: None,
+                }],
+
+                Err(_) => Vec::new(),
+            },
+            jwks: env::try_parse(JWKS).ok(),
+            audience: util::list_env(AUDIENCE).unwrap_or_default(),
+            prefixes: util::list_env(PREFIXES).unwrap_or_else(__default_prefixes),
+            claim: env::try_parse_or_else(CLAIM, __default_claim())?,
+            leeway: env::try_parse_or_else(LEEWAY, __default_leeway())?,
+        })
+    }
+}
+
+/// A key that tokens can be signed with.
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Key {
+    /// ID of the key. If set, then tokens with a `kid` header are only checked against
+    /// the key with the same ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// The algorithm that tokens are signed with.
+    pub algorithm: Algorithm,
+
+    /// The shared secret, for `HS256`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+
+    /// Location to a PEM-encoded public key, for `RS256` and `EdDSA`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<PathBuf>,
+}
+
+/// An algorithm that tokens can be signed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Algorithm {
+    HS256,
+    RS256,
+    EdDSA,
+}
+
+impl Merge for Algorithm {
+    fn merge(&mut self, other: Self) {
+        *self = other;
+    }
+}
+
+#[inline]
+fn __default_prefixes() -> Vec<String> {
+    vec![String::from("/")]
+}
+
+#[inline]
+fn __default_claim() -> String {
+    String::from("prefixes")
+}
+
+#[inline]
+const fn __default_leeway() -> u64 {
+    60
+}
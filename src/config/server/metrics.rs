@@ -0,0 +1,76 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{env::TryFromEnv, merge::Merge};
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+
+pub const ENABLED: &str = "HAZEL_SERVER_METRICS";
+pub const PATH: &str = "HAZEL_SERVER_METRICS_PATH";
+pub const LISTEN: &str = "HAZEL_SERVER_METRICS_LISTEN";
+
+/// ## `[server.metrics]` table
+/// Exposes Prometheus metrics about the requests that are served and the calls that
+/// are made to the storage backend.
+///
+/// ```toml
+/// [server.metrics]
+/// path = "/metrics"
+/// listen = "127.0.0.1:9090"
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Path that the metrics are served on.
+    #[serde(default = "__default_path")]
+    pub path: String,
+
+    /// Address of a separate listener to serve the metrics on, so that they aren't
+    /// reachable from the same address as objects. If this isn't set, then they're
+    /// served by the main server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen: Option<SocketAddr>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            path: __default_path(),
+            listen: None,
+        }
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            path: util::env_from_str(PATH, __default_path())?,
+            listen: std::env::var(LISTEN)
+                .ok()
+                .map(|addr| addr.parse())
+                .transpose()
+                .map_err(|e| eyre!("failed to parse environment variable `${}`: {}", LISTEN, e))?,
+        })
+    }
+}
+
+#[inline]
+fn __default_path() -> String {
+    String::from("/metrics")
+}
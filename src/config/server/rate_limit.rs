@@ -0,0 +1,142 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::merge::Merge;
+use serde::{Deserialize, Serialize};
+
+/// ## `[[server.rate_limits]]` array of tables
+/// Limits how many requests, bytes or concurrent requests each client (or prefix) can
+/// make, with token buckets that refill continuously.
+///
+/// ```toml
+/// [[server.rate_limits]]
+/// key = "ip"
+/// requests_per_second = 10
+/// burst = 50
+/// bytes_per_second = 10485760
+/// concurrency = 4
+/// ```
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// What requests share a budget.
+    #[serde(default)]
+    pub key: Key,
+
+    /// List of path prefixes that this rule applies to. If empty, then this rule
+    /// applies to all paths.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefixes: Vec<String>,
+
+    /// Amount of requests that are allowed per second, on average.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_second: Option<f64>,
+
+    /// Amount of requests that can be made at once before being limited to
+    /// [`Rule::requests_per_second`]. Defaults to one second's worth of requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst: Option<u32>,
+
+    /// Amount of response body bytes that can be sent per second, on average. Responses
+    /// are never cut off: requests are rejected once their budget is used up, until it
+    /// is refilled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_per_second: Option<u64>,
+
+    /// Amount of bytes that can be sent at once before being limited to
+    /// [`Rule::bytes_per_second`]. Defaults to one second's worth of bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst_bytes: Option<u64>,
+
+    /// Maximum amount of requests that can be in flight at once, including the ones
+    /// whose responses are still being sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<u32>,
+}
+
+impl Rule {
+    /// Checks if this rule applies to requests to `path`.
+    pub fn matches(&self, path: &str) -> bool {
+        self.prefix_for(path).is_some()
+    }
+
+    /// Returns the configured prefix that `path` is in, or `/` if this rule applies to
+    /// all paths.
+    pub fn prefix_for(&self, path: &str) -> Option<&str> {
+        match self.prefixes.as_slice() {
+            [] => Some("/"),
+            prefixes => prefixes
+                .iter()
+                .find(|prefix| util::has_path_prefix(path, prefix))
+                .map(String::as_str),
+        }
+    }
+
+    /// Rejects prefixes that don't start with a `/`, as they would never match, and rates
+    /// that a budget could never be refilled at.
+    pub fn validate(&self) -> eyre::Result<()> {
+        util::check_prefixes("server.rate_limits.prefixes", &self.prefixes)?;
+
+        if let Some(rate) = self.requests_per_second &&
+            !(rate > 0.0 && rate.is_finite())
+        {
+            bail!("`server.rate_limits.requests_per_second` is {rate}, but it has to be a positive number");
+        }
+
+        if self.bytes_per_second == Some(0) {
+            bail!("`server.rate_limits.bytes_per_second` is 0, but it has to be a positive number");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the amount of requests that can be made at once.
+    pub fn request_burst(&self) -> Option<f64> {
+        let rate = self.requests_per_second?;
+        Some(self.burst.map(f64::from).unwrap_or_else(|| rate.ceil().max(1.0)))
+    }
+
+    /// Returns the amount of bytes that can be sent at once.
+    pub fn byte_burst(&self) -> Option<f64> {
+        let rate = self.bytes_per_second?;
+        Some(self.burst_bytes.unwrap_or(rate) as f64)
+    }
+}
+
+/// What requests share the budget of a [`Rule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Key {
+    /// Requests from the same client IP address.
+    #[default]
+    Ip,
+
+    /// Requests from the same authenticated user (the Basic authentication user, or
+    /// the bearer token's `sub` claim). Unauthenticated requests are keyed by their
+    /// client IP address.
+    Identity,
+
+    /// Requests to the same configured prefix, from all clients.
+    Prefix,
+}
+
+impl Merge for Key {
+    fn merge(&mut self, other: Self) {
+        if other != Key::default() {
+            *self = other;
+        }
+    }
+}
@@ -0,0 +1,161 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{env::TryFromEnv, merge::Merge};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+pub const HEADER: &str = "HAZEL_SERVER_REQUEST_ID_HEADER";
+pub const TRUST_INCOMING: &str = "HAZEL_SERVER_REQUEST_ID_TRUST_INCOMING";
+pub const MAX_LENGTH: &str = "HAZEL_SERVER_REQUEST_ID_MAX_LENGTH";
+pub const FORMAT: &str = "HAZEL_SERVER_REQUEST_ID_FORMAT";
+
+/// ## `[server.request_id]` table
+/// Configures the ID that identifies each request, which is sent back in a response
+/// header, included in logs and error bodies, and passed on to the storage backend.
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Name of the header that the request ID is read from and sent back in.
+    #[serde(default = "__default_header")]
+    pub header: String,
+
+    /// Whether if the request ID that the client (or a load balancer in front of
+    /// Hazel) sent is used instead of generating a new one. IDs that are too long or
+    /// contain characters other than `A-Z`, `a-z`, `0-9` and `-_.:=+/` are ignored.
+    #[serde(default = "__default_trust_incoming")]
+    pub trust_incoming: bool,
+
+    /// Longest incoming request ID that is accepted.
+    #[serde(default = "__default_max_length")]
+    pub max_length: usize,
+
+    /// Format of the request IDs that Hazel generates.
+    #[serde(default)]
+    pub format: Format,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            header: __default_header(),
+            trust_incoming: __default_trust_incoming(),
+            max_length: __default_max_length(),
+            format: Format::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Checks if `id` can be used as a request ID.
+    pub fn is_valid(&self, id: &str) -> bool {
+        !id.is_empty() &&
+            id.len() <= self.max_length &&
+            id.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.:=+/".contains(&b))
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            header: util::env_from_str(HEADER, __default_header())?,
+            trust_incoming: util::env_from_str(TRUST_INCOMING, __default_trust_incoming())?,
+            max_length: util::env_from_str(MAX_LENGTH, __default_max_length())?,
+            format: util::env_from_str(FORMAT, Format::default())?,
+        })
+    }
+}
+
+/// Format of generated request IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// 12 random alphanumeric characters, like `Xb2kQ9aLmP0z`.
+    #[default]
+    Alphanumeric,
+
+    /// A time-ordered UUID (version 7), like `01890a5d-ac96-774b-bcce-b302099a8057`.
+    #[serde(alias = "uuidv7")]
+    Uuid,
+
+    /// A [ULID](https://github.com/ulid/spec), like `01ARZ3NDEKTSV4RRFFQ69G5FAV`.
+    Ulid,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.trim().to_ascii_lowercase() {
+            "alphanumeric" => Ok(Format::Alphanumeric),
+            "uuid" | "uuidv7" => Ok(Format::Uuid),
+            "ulid" => Ok(Format::Ulid),
+            input => Err(format!(
+                "unknown request id format `{input}`: expected `alphanumeric`, `uuid` or `ulid`"
+            )),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Alphanumeric => f.write_str("alphanumeric"),
+            Format::Uuid => f.write_str("uuid"),
+            Format::Ulid => f.write_str("ulid"),
+        }
+    }
+}
+
+impl Merge for Format {
+    fn merge(&mut self, other: Self) {
+        if other != Format::default() {
+            *self = other;
+        }
+    }
+}
+
+#[inline]
+fn __default_header() -> String {
+    String::from("x-request-id")
+}
+
+const fn __default_trust_incoming() -> bool {
+    true
+}
+
+const fn __default_max_length() -> usize {
+    128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn test_is_valid() {
+        let config = Config::default();
+        assert!(config.is_valid("Xb2kQ9aLmP0z"));
+        assert!(config.is_valid("01890a5d-ac96-774b-bcce-b302099a8057"));
+        assert!(config.is_valid("Root=1-67891233-abcdef012345678912345678"));
+        assert!(!config.is_valid(""));
+        assert!(!config.is_valid("has spaces"));
+        assert!(!config.is_valid("<script>"));
+        assert!(!config.is_valid(&"a".repeat(129)));
+    }
+}
@@ -0,0 +1,165 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::util;
+use azalia::config::{
+    env::{self, TryFromEnv},
+    merge::Merge,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const ENABLED: &str = "HAZEL_SERVER_SIGNING";
+pub const KEYS: &str = "HAZEL_SERVER_SIGNING_KEYS";
+pub const DEFAULT_KEY: &str = "HAZEL_SERVER_SIGNING_DEFAULT_KEY";
+pub const PREFIXES: &str = "HAZEL_SERVER_SIGNING_PREFIXES";
+
+/// ## `[server.signing]` table
+/// Requires requests in some prefixes to be made with a signed URL, see
+/// [`crate::signing`].
+#[derive(Debug, Clone, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Secret keys that signed URLs can be signed with, keyed by their ID. Keys can be
+    /// rotated by adding a new key, signing new URLs with it, and removing the old key
+    /// once the URLs that were signed with it have expired.
+    #[serde(default)]
+    pub keys: BTreeMap<String, String>,
+
+    /// ID of the key that `hazel sign` signs URLs with. If not set, then the first key
+    /// (ordered by ID) is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_key: Option<String>,
+
+    /// List of path prefixes where requests need a signed URL. A prefix of `/` requires
+    /// it for every object.
+    #[serde(default = "__default_prefixes")]
+    pub prefixes: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keys: BTreeMap::new(),
+            default_key: None,
+            prefixes: __default_prefixes(),
+        }
+    }
+}
+
+impl Config {
+    /// Checks if requests to `path` need a signed URL.
+    pub fn is_required_for(&self, path: &str) -> bool {
+        self.prefixes.iter().any(|prefix| util::has_path_prefix(path, prefix))
+    }
+
+    /// Rejects a configuration that would quietly reject every request: prefixes without
+    /// any keys to verify them with, or a `default_key` that isn't one of the keys. Also
+    /// rejects prefixes that don't start with a `/`, as they would never match.
+    pub fn validate(&self) -> eyre::Result<()> {
+        util::check_prefixes("server.signing.prefixes", &self.prefixes)?;
+
+        if self.keys.is_empty() && !self.prefixes.is_empty() {
+            bail!("`server.signing.keys` is empty, so no signed URL could ever be verified");
+        }
+
+        if let Some(ref id) = self.default_key &&
+            !self.keys.contains_key(id)
+        {
+            bail!("`server.signing.default_key` is `{id}`, which isn't one of `server.signing.keys`");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the ID and secret of the key that new URLs are signed with.
+    pub fn signing_key(&self) -> Option<(&str, &str)> {
+        match self.default_key {
+            Some(ref id) => self.keys.get_key_value(id),
+            None => self.keys.iter().next(),
+        }
+        .map(|(id, secret)| (id.as_str(), secret.as_str()))
+    }
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            keys: keys_from_env()?,
+            default_key: env::try_parse_optional(DEFAULT_KEY)?,
+            prefixes: util::list_env(PREFIXES).unwrap_or_else(__default_prefixes),
+        })
+    }
+}
+
+/// Parses `HAZEL_SERVER_SIGNING_KEYS`, a comma-separated list of `id=secret` pairs.
+/// Secrets can contain `=` (like base64 padding), but malformed pairs are rejected
+/// rather than skipped. The error never includes the secret.
+fn keys_from_env() -> eyre::Result<BTreeMap<String, String>> {
+    let Some(value) = env::try_parse_optional::<_, String>(KEYS)? else {
+        return Ok(BTreeMap::new());
+    };
+
+    value
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .enumerate()
+        .map(|(index, pair)| match pair.split_once('=') {
+            Some((id, secret)) if !id.trim().is_empty() && !secret.is_empty() => {
+                Ok((id.trim().to_owned(), secret.to_owned()))
+            }
+
+            _ => Err(eyre!(
+                "`${KEYS}` has to be a comma-separated list of `id=secret` pairs, but pair #{} isn't",
+                index + 1
+            )),
+        })
+        .collect()
+}
+
+#[inline]
+fn __default_prefixes() -> Vec<String> {
+    vec![String::from("/")]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn test_validate() {
+        let config = |toml: &str| toml::from_str::<Config>(toml).unwrap();
+
+        assert!(
+            config("keys = { a = \"secret\" }\ndefault_key = \"a\"")
+                .validate()
+                .is_ok()
+        );
+        assert!(config("prefixes = []").validate().is_ok());
+        assert!(config("prefixes = [\"/private\"]").validate().is_err());
+        assert!(
+            config("keys = { a = \"secret\" }\nprefixes = [\"private\"]")
+                .validate()
+                .is_err()
+        );
+        assert!(
+            config("keys = { a = \"secret\" }\ndefault_key = \"b\"")
+                .validate()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,46 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::opentelemetry;
+use crate::config::util;
+use azalia::config::{env::TryFromEnv, merge::Merge};
+use serde::{Deserialize, Serialize};
+
+pub const OPENTELEMETRY: &str = "HAZEL_TRACING_OTEL";
+
+/// ## `[tracing]` table
+/// Configures where the spans that Hazel creates for each request are exported to.
+#[derive(Debug, Clone, Default, Merge, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Exports spans to an OpenTelemetry collector over OTLP. This is disabled by
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opentelemetry: Option<opentelemetry::Config>,
+}
+
+impl TryFromEnv for Config {
+    type Error = eyre::Report;
+
+    fn try_from_env() -> Result<Self, Self::Error> {
+        Ok(Config {
+            opentelemetry: match util::bool_env(OPENTELEMETRY) {
+                Ok(true) => opentelemetry::Config::try_from_env().map(Some)?,
+                Ok(false) => None,
+                Err(e) => return Err(e),
+            },
+        })
+    }
+}
@@ -47,3 +47,33 @@ pub fn env_from_result<T>(result: Result<T, VarError>, default: T) -> eyre::Resu
 pub fn bool_env(key: &str) -> eyre::Result<bool> {
     env_from_result(std::env::var(key).map(|x| azalia::TRUTHY_REGEX.is_match(&x)), false)
 }
+
+/// Parses a comma-separated list from the environment variable `key`, or returns
+/// `None` if it isn't set.
+pub fn list_env(key: &str) -> Option<Vec<String>> {
+    std::env::var(key).ok().map(|value| {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect()
+    })
+}
+
+/// Checks if `path` is `prefix` or is inside of it, respecting path segments: `/a/b`
+/// is inside `/a` but `/ab` isn't.
+pub fn has_path_prefix(path: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Rejects any of `prefixes` that doesn't start with a `/`, since it could never match
+/// a request's path. `setting` is the name of the setting, like `server.jwt.prefixes`.
+pub fn check_prefixes(setting: &str, prefixes: &[String]) -> eyre::Result<()> {
+    match prefixes.iter().find(|prefix| !prefix.starts_with('/')) {
+        Some(prefix) => bail!("`{setting}` has `{prefix}`, but every prefix has to start with a `/`"),
+        None => Ok(()),
+    }
+}
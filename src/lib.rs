@@ -14,7 +14,12 @@
 // limitations under the License.
 
 pub mod config;
+pub mod logging;
+pub mod metrics;
 pub mod server;
+pub mod signing;
+pub mod storage;
+pub mod telemetry;
 
 #[macro_use]
 extern crate tracing;
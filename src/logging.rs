@@ -0,0 +1,312 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sinks that logs are written to besides stdout, and log files that are rotated by
+//! their size or on an interval.
+
+mod syslog;
+
+use crate::config::{self, logging::Rotation};
+use chrono::{DateTime, Utc};
+use eyre::Context;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::{Level, Subscriber, level_filters::LevelFilter};
+use tracing_subscriber::{
+    Layer,
+    filter::{FilterExt, filter_fn},
+    fmt,
+    layer::Filter,
+    registry::LookupSpan,
+};
+
+/// Builds the filter of a sink that logs events up to `level`, or up to `logging.level`
+/// if the sink doesn't have a level of its own. If `logging.filter` is set, then it
+/// applies to every sink and their levels only cap it.
+pub fn filter<S>(
+    config: &config::logging::Config,
+    level: Option<Level>,
+) -> eyre::Result<Box<dyn Filter<S> + Send + Sync>>
+where
+    S: Subscriber + for<'l> LookupSpan<'l>,
+{
+    // disallow from getting logs from `tokio` since it doesn't contain anything
+    // useful to us
+    let not_tokio = filter_fn(|meta| !meta.target().starts_with("tokio::"));
+
+    Ok(match config.filter {
+        Some(ref filter) if !filter.is_only_level_filter() => match level {
+            Some(level) => filter
+                .to_env_filter()?
+                .and(LevelFilter::from_level(level))
+                .and(not_tokio)
+                .boxed(),
+
+            None => filter.to_env_filter()?.and(not_tokio).boxed(),
+        },
+
+        _ => LevelFilter::from_level(level.unwrap_or(config.level))
+            .and(not_tokio)
+            .boxed(),
+    })
+}
+
+/// Builds the layers of the file, syslog and journald sinks that are configured.
+pub fn sinks<S>(config: &config::logging::Config) -> eyre::Result<Vec<Box<dyn Layer<S> + Send + Sync>>>
+where
+    S: Subscriber + for<'l> LookupSpan<'l>,
+{
+    let mut sinks = Vec::new();
+    if let Some(ref file) = config.file {
+        let writer = RotatingFile::open(&file.path, file.rotation.clone())
+            .with_context(|| format!("failed to open log file `{}`", file.path.display()))?;
+
+        let layer = fmt::layer().with_ansi(false).with_writer(Mutex::new(writer));
+        sinks.push(match file.json {
+            true => layer.json().with_filter(filter(config, file.level)?).boxed(),
+            false => layer.with_filter(filter(config, file.level)?).boxed(),
+        });
+    }
+
+    if let Some(ref options) = config.syslog {
+        let writer = syslog::Syslog::connect(options)
+            .with_context(|| format!("failed to connect to syslog at `{}`", options.address))?;
+
+        // the timestamp and level are already in the header of each message
+        let layer = fmt::layer()
+            .with_ansi(false)
+            .without_time()
+            .with_level(false)
+            .with_writer(writer);
+
+        sinks.push(match options.json {
+            true => layer.json().with_filter(filter(config, options.level)?).boxed(),
+            false => layer.with_filter(filter(config, options.level)?).boxed(),
+        });
+    }
+
+    if let Some(ref journald) = config.journald {
+        let layer = tracing_journald::layer()
+            .context("failed to connect to the systemd journal")?
+            .with_syslog_identifier(journald.identifier.clone());
+
+        sinks.push(layer.with_filter(filter(config, journald.level)?).boxed());
+    }
+
+    Ok(sinks)
+}
+
+/// A file that is appended to, and is renamed to `<file>.<timestamp>` whenever it
+/// gets too big or its [`Rotation::interval`] has passed. Only the latest
+/// [`Rotation::max_files`] rotated files are kept.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    header: Option<fn() -> String>,
+    file: File,
+    size: u64,
+    period: Option<i64>,
+}
+
+impl RotatingFile {
+    /// Opens the file at `path` for appending, creating it and its directory if they
+    /// don't exist.
+    pub fn open<P: Into<PathBuf>>(path: P, rotation: Rotation) -> io::Result<RotatingFile> {
+        let path = path.into();
+        if let Some(parent) = path.parent() &&
+            !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = append(&path)?;
+        let metadata = file.metadata()?;
+
+        // a file that was left behind by a previous run belongs to the interval that
+        // it was last written in, so that it's rotated if that one has passed
+        let modified = metadata
+            .modified()
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+        Ok(RotatingFile {
+            period: period(&rotation, modified),
+            size: metadata.len(),
+            header: None,
+            rotation,
+            path,
+            file,
+        })
+    }
+
+    /// Writes the output of `header` at the start of every new file.
+    pub fn with_header(mut self, header: fn() -> String) -> io::Result<RotatingFile> {
+        self.header = Some(header);
+        if self.size == 0 {
+            self.write_header()?;
+        }
+
+        Ok(self)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if let Some(header) = self.header {
+            let header = header();
+            self.file.write_all(header.as_bytes())?;
+            self.size += header.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        self.file.flush()?;
+
+        let stamp = now.format("%Y%m%dT%H%M%S").to_string();
+        let mut rotated = with_suffix(&self.path, &stamp);
+        let mut attempt = 1;
+        while rotated.try_exists()? {
+            rotated = with_suffix(&self.path, &format!("{stamp}.{attempt}"));
+            attempt += 1;
+        }
+
+        fs::rename(&self.path, &rotated)?;
+        self.file = append(&self.path)?;
+        self.size = 0;
+        self.write_header()?;
+        self.prune()
+    }
+
+    /// Deletes the oldest rotated files, so that only [`Rotation::max_files`] are kept.
+    fn prune(&self) -> io::Result<()> {
+        if self.rotation.max_files == 0 {
+            return Ok(());
+        }
+
+        let Some(name) = self.path.file_name().and_then(|name| name.to_str()) else {
+            return Ok(());
+        };
+
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let prefix = format!("{name}.");
+        let mut rotated = fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry.file_name().to_str().is_some_and(|file| {
+                    file.strip_prefix(&prefix)
+                        .is_some_and(|stamp| stamp.starts_with(|c: char| c.is_ascii_digit()))
+                })
+            })
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+
+        // the timestamps sort in the order that the files were rotated in
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(self.rotation.max_files);
+        for path in &rotated[..excess] {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = Utc::now();
+        let period = period(&self.rotation, now);
+        let oversized = self.rotation.max_size > 0 && self.size + buf.len() as u64 > self.rotation.max_size;
+
+        // an empty file is never rotated, even if a single write doesn't fit in it
+        if self.size > 0 && (oversized || period != self.period) {
+            self.rotate(now)?;
+        }
+
+        self.period = period;
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn period(rotation: &Rotation, time: DateTime<Utc>) -> Option<i64> {
+    rotation
+        .interval
+        .as_secs()
+        .map(|secs| time.timestamp().div_euclid(secs))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RotatingFile;
+    use crate::config::logging::{Interval, Rotation};
+    use std::{fs, io::Write};
+
+    #[test]
+    fn test_rotate_by_size() {
+        let dir = std::env::temp_dir().join(format!("hazel-rotate-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let rotation = Rotation {
+            max_size: 10,
+            interval: Interval::Never,
+            max_files: 1,
+        };
+
+        let mut file = RotatingFile::open(dir.join("access.log"), rotation).unwrap();
+        file.write_all(b"first\n").unwrap();
+        file.write_all(b"second\n").unwrap();
+        file.write_all(b"third\n").unwrap();
+
+        let mut rotated = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name != "access.log")
+            .collect::<Vec<_>>();
+
+        assert_eq!(fs::read_to_string(dir.join("access.log")).unwrap(), "third\n");
+        assert_eq!(rotated.len(), 1);
+        assert_eq!(
+            fs::read_to_string(dir.join(rotated.pop().unwrap())).unwrap(),
+            "second\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
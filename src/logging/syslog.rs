@@ -0,0 +1,214 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sends logs to a syslog server as [RFC 5424] messages.
+//!
+//! [RFC 5424]: https://www.rfc-editor.org/rfc/rfc5424
+
+use crate::config::logging::syslog::{Config, Facility};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::{
+    io::{self, Write},
+    net::{ToSocketAddrs, UdpSocket},
+};
+#[cfg(unix)]
+use std::{os::unix::net::UnixDatagram, path::PathBuf};
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// A [`MakeWriter`] that sends every log as a datagram to a syslog server.
+pub struct Syslog {
+    transport: Transport,
+    facility: Facility,
+    hostname: String,
+    app_name: String,
+    pid: u32,
+}
+
+enum Transport {
+    Udp(UdpSocket),
+
+    #[cfg(unix)]
+    Unix(UnixDatagram, PathBuf),
+}
+
+impl Syslog {
+    /// Creates the socket that messages are sent to `config.address` with.
+    pub fn connect(config: &Config) -> eyre::Result<Syslog> {
+        let address = &config.address;
+        let transport = match address.scheme() {
+            "udp" => {
+                let host = address
+                    .host_str()
+                    .ok_or_else(|| eyre!("syslog address `{address}` is missing a host"))?;
+
+                let addr = (host, address.port().unwrap_or(514))
+                    .to_socket_addrs()?
+                    .next()
+                    .ok_or_else(|| eyre!("syslog address `{address}` didn't resolve to anything"))?;
+
+                let socket = UdpSocket::bind(match addr.is_ipv4() {
+                    true => "0.0.0.0:0",
+                    false => "[::]:0",
+                })?;
+
+                socket.connect(addr)?;
+                Transport::Udp(socket)
+            }
+
+            #[cfg(unix)]
+            "unix" => Transport::Unix(UnixDatagram::unbound()?, PathBuf::from(address.path())),
+
+            scheme => bail!("unsupported syslog address scheme `{scheme}`: expected `udp` or `unix`"),
+        };
+
+        Ok(Syslog {
+            transport,
+            facility: config.facility,
+            hostname: hostname(),
+            app_name: printable(&config.app_name, 48),
+            pid: std::process::id(),
+        })
+    }
+
+    /// Returns the header of a message with the given `level` that was logged at `time`.
+    fn header(&self, level: Level, time: DateTime<Utc>) -> String {
+        let severity = match level {
+            Level::ERROR => 3,
+            Level::WARN => 4,
+            Level::INFO => 6,
+            Level::DEBUG | Level::TRACE => 7,
+        };
+
+        format!(
+            "<{}>1 {} {} {} {} - - ",
+            self.facility.code() * 8 + severity,
+            time.to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.hostname,
+            self.app_name,
+            self.pid
+        )
+    }
+
+    fn send(&self, datagram: &[u8]) {
+        // there's nowhere to report a failure to, since this is the logger
+        let _ = match self.transport {
+            Transport::Udp(ref socket) => socket.send(datagram),
+
+            #[cfg(unix)]
+            Transport::Unix(ref socket, ref path) => socket.send_to(datagram, path),
+        };
+    }
+}
+
+impl<'a> MakeWriter<'a> for Syslog {
+    type Writer = Message<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        Message {
+            syslog: self,
+            level: Level::INFO,
+            buf: Vec::new(),
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        Message {
+            syslog: self,
+            level: *meta.level(),
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// A single message, which is sent once it has been written.
+pub struct Message<'a> {
+    syslog: &'a Syslog,
+    level: Level,
+    buf: Vec<u8>,
+}
+
+impl Write for Message<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Message<'_> {
+    fn drop(&mut self) {
+        let message = self.buf.trim_ascii_end();
+        if message.is_empty() {
+            return;
+        }
+
+        let mut datagram = self.syslog.header(self.level, Utc::now()).into_bytes();
+        datagram.extend_from_slice(message);
+        self.syslog.send(&datagram);
+    }
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|hostname| printable(hostname.trim(), 255))
+        .filter(|hostname| !hostname.is_empty())
+        .unwrap_or_else(|| String::from("-"))
+}
+
+/// Truncates `value` to `max` characters and replaces the ones that aren't allowed in
+/// header fields (anything other than printable ASCII) with `_`.
+fn printable(value: &str, max: usize) -> String {
+    value
+        .chars()
+        .take(max)
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Syslog, Transport};
+    use crate::config::logging::syslog::Facility;
+    use chrono::{TimeZone, Utc};
+    use std::net::UdpSocket;
+    use tracing::Level;
+
+    #[test]
+    fn test_header() {
+        let syslog = Syslog {
+            transport: Transport::Udp(UdpSocket::bind("127.0.0.1:0").unwrap()),
+            facility: Facility::Local3,
+            hostname: String::from("cdn-1"),
+            app_name: String::from("hazel"),
+            pid: 42,
+        };
+
+        let time = Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(
+            syslog.header(Level::WARN, time),
+            "<156>1 2025-01-02T03:04:05.000000Z cdn-1 hazel 42 - - "
+        );
+
+        assert_eq!(
+            syslog.header(Level::INFO, time),
+            "<158>1 2025-01-02T03:04:05.000000Z cdn-1 hazel 42 - - "
+        );
+    }
+}
@@ -0,0 +1,234 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Prometheus metrics about the requests that Hazel serves and the calls that it
+//! makes to the storage backend, exposed by the `[server.metrics]` endpoint.
+
+use prometheus_client::{
+    encoding::{EncodeLabelSet, text},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{Histogram, exponential_buckets},
+    },
+    registry::Registry,
+};
+use std::{sync::LazyLock, time::Duration};
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Content type of the text that [`encode`] returns.
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+type HistogramFamily<L> = Family<L, Histogram, fn() -> Histogram>;
+
+pub struct Metrics {
+    registry: Registry,
+    requests: Family<RequestLabels, Counter>,
+    request_duration: HistogramFamily<RequestLabels>,
+    in_flight: Gauge,
+    served_bytes: Counter,
+    storage_duration: HistogramFamily<StorageLabels>,
+    storage_errors: Family<StorageLabels, Counter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct RequestLabels {
+    method: String,
+    route: String,
+    status: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct StorageLabels {
+    backend: &'static str,
+    operation: &'static str,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let mut registry = Registry::with_prefix("hazel");
+        let metrics = Metrics {
+            requests: Family::default(),
+            request_duration: Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.001, 2.0, 16))),
+            in_flight: Gauge::default(),
+            served_bytes: Counter::default(),
+            storage_duration: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.0005, 2.0, 16))
+            }),
+            storage_errors: Family::default(),
+            registry: Registry::default(),
+        };
+
+        registry.register(
+            "http_requests",
+            "Amount of HTTP requests that were handled",
+            metrics.requests.clone(),
+        );
+
+        registry.register(
+            "http_request_duration_seconds",
+            "How long it took to respond to HTTP requests, until the response's headers were sent",
+            metrics.request_duration.clone(),
+        );
+
+        registry.register(
+            "http_requests_in_flight",
+            "Amount of HTTP requests that are being handled",
+            metrics.in_flight.clone(),
+        );
+
+        registry.register(
+            "served_bytes",
+            "Amount of bytes of objects that were served",
+            metrics.served_bytes.clone(),
+        );
+
+        registry.register(
+            "storage_operation_duration_seconds",
+            "How long calls to the storage backend took",
+            metrics.storage_duration.clone(),
+        );
+
+        registry.register(
+            "storage_operation_errors",
+            "Amount of calls to the storage backend that failed",
+            metrics.storage_errors.clone(),
+        );
+
+        Metrics { registry, ..metrics }
+    }
+}
+
+/// Tracks a request that is being handled until [`InFlight::finish`] is called.
+pub struct InFlight(());
+
+impl InFlight {
+    pub fn start() -> InFlight {
+        METRICS.in_flight.inc();
+        InFlight(())
+    }
+
+    /// Records a request to `route` that was responded to with `status` after `elapsed`.
+    pub fn finish(self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let labels = RequestLabels {
+            method: method.to_owned(),
+            route: route.to_owned(),
+            status,
+        };
+
+        METRICS.requests.get_or_create(&labels).inc();
+        METRICS
+            .request_duration
+            .get_or_create(&labels)
+            .observe(elapsed.as_secs_f64());
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        METRICS.in_flight.dec();
+    }
+}
+
+/// Records that `bytes` bytes of an object were served.
+pub fn served(bytes: usize) {
+    METRICS.served_bytes.inc_by(bytes as u64);
+}
+
+/// Records a call to the storage `backend` that took `elapsed`.
+pub fn storage_operation(backend: &'static str, operation: &'static str, elapsed: Duration, failed: bool) {
+    let labels = StorageLabels { backend, operation };
+    METRICS
+        .storage_duration
+        .get_or_create(&labels)
+        .observe(elapsed.as_secs_f64());
+
+    if failed {
+        METRICS.storage_errors.get_or_create(&labels).inc();
+    }
+}
+
+/// Encodes every metric in the OpenMetrics text format.
+pub fn encode() -> String {
+    let mut buf = String::new();
+    text::encode(&mut buf, &METRICS.registry).expect("writing to a string can't fail");
+
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{get, router, send, text};
+    use axum::http::{StatusCode, header};
+    use std::fs;
+
+    #[test]
+    fn test_scrape() {
+        let (dir, router) = router("metrics", "[server.metrics]");
+
+        assert_eq!(text(send(&router, "/public/a.txt", &[])), "hello");
+
+        let res = send(&router, "/metrics", &[]);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], super::CONTENT_TYPE);
+
+        let body = text(res);
+        let labels = r#"{method="GET",route="/{*file}",status="200"}"#;
+        // other tests share the metrics, so the values are only known to be at least ours
+        let value = |name: &str| {
+            body.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+                .unwrap_or_else(|| panic!("`{name}` isn't in:\n{body}"))
+                .parse::<f64>()
+                .unwrap()
+        };
+
+        assert!(body.contains("# TYPE hazel_http_requests counter"));
+        assert!(value(&format!("hazel_http_requests_total{labels}")) >= 1.0);
+
+        assert!(body.contains("# TYPE hazel_http_request_duration_seconds histogram"));
+        assert!(value(&format!("hazel_http_request_duration_seconds_count{labels}")) >= 1.0);
+        assert!(
+            value(
+                r#"hazel_http_request_duration_seconds_bucket{le="+Inf",method="GET",route="/{*file}",status="200"}"#
+            ) >= 1.0
+        );
+
+        assert!(body.contains("# TYPE hazel_http_requests_in_flight gauge"));
+        assert!(value("hazel_served_bytes_total") >= 5.0);
+        assert!(
+            value(r#"hazel_storage_operation_duration_seconds_count{backend="filesystem",operation="stat"}"#) >=
+                1.0
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_scrape_access_rules() {
+        let (dir, router) = router(
+            "metrics-access",
+            "[server.metrics]\n\n[[server.access_rules]]\naction = \"deny\"\nprefixes = [\"/metrics\"]",
+        );
+
+        assert_eq!(get(&router, "/metrics", &[]), StatusCode::FORBIDDEN);
+        assert_eq!(get(&router, "//metrics", &[]), StatusCode::FORBIDDEN);
+        assert_eq!(get(&router, "/public/a.txt", &[]), StatusCode::OK);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
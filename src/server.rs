@@ -13,52 +13,166 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::config::{Config, server::ssl};
-use axum::Router;
-use axum_server::{Address, Handle, tls_rustls::RustlsConfig};
-use azalia::remi::StorageService;
+use crate::{
+    config::{Config, server::ssl},
+    storage::{Caches, Storage},
+};
+use axum::{Router, http::HeaderName};
+use axum_server::{
+    Address, Handle,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
 use eyre::Context;
+use proxy::ProxyProtocolAcceptor;
 use std::{net::SocketAddr, time::Duration};
 
+mod access_log;
+mod autoindex;
+mod basic_auth;
+pub mod canonical;
+mod compression;
+mod conditional;
+mod errors;
+mod hosts;
+mod jwt;
 mod middlewares;
+mod mounts;
+mod negotiate;
+mod proxy;
+mod range;
+mod rate_limit;
 mod routes;
 
-pub async fn start(storage: StorageService, config: Config) -> eyre::Result<()> {
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub async fn start(storage: Storage, config: Config) -> eyre::Result<()> {
     info!("starting HTTP server!");
 
-    let router = routes::create_router(storage, config.clone());
+    let header = &config.server.request_id.header;
+    HeaderName::try_from(header)
+        .with_context(|| format!("`server.request_id.header` is not a valid header name: {header}"))?;
+
+    let router = router(storage, &config).await?;
+    if let Some(ref metrics) = config.server.metrics &&
+        let Some(addr) = metrics.listen
+    {
+        start_metrics_server(addr, &metrics.path).await?;
+    }
+
     match config.server.ssl {
         Some(ref ssl) => start_https_server(&config, ssl, router).await,
         None => start_http_server(&config, router).await,
     }
 }
 
+/// Creates the router that serves `storage` and everything else that `config` sets up,
+/// like its mounts, virtual hosts and the checks that requests go through.
+async fn router(storage: Storage, config: &Config) -> eyre::Result<Router> {
+    let jwt = config.server.jwt.as_ref().map(jwt::Verifier::load).transpose()?;
+    let realms = basic_auth::Realms::load(&config.server.basic_auth)?;
+    let limiter = rate_limit::Limiter::new(&config.server.rate_limits);
+    let access_log = config
+        .server
+        .access_log
+        .as_ref()
+        .map(access_log::AccessLog::open)
+        .transpose()?;
+
+    let caches = Caches::new(&config.server)?;
+    let storage = caches.attach(storage);
+
+    let mounts = mounts::Mounts::new(config, &caches)?;
+    mounts.init().await?;
+
+    let hosts = hosts::Hosts::new(config, &caches)?;
+    hosts.init().await?;
+
+    Ok(routes::create_router(
+        storage,
+        config.clone(),
+        jwt,
+        realms,
+        limiter,
+        access_log,
+        mounts,
+        hosts,
+    ))
+}
+
 async fn start_https_server(config: &Config, ssl: &ssl::Config, router: Router) -> eyre::Result<()> {
     let handle = Handle::new();
     tokio::spawn(shutdown_signal(Some(handle.clone())));
 
     let addr = config.server.to_socket_addr();
-    let config = RustlsConfig::from_pem_file(&ssl.cert, &ssl.cert_key).await?;
+    let tls = RustlsAcceptor::new(RustlsConfig::from_pem_file(&ssl.cert, &ssl.cert_key).await?);
+    let server = axum_server::bind(addr).handle(handle);
 
-    info!(address = %addr, "listening on HTTPS");
-    axum_server::bind_rustls(addr, config)
-        .handle(handle)
-        .serve(router.into_make_service())
-        .await
-        .context("failed to run HTTPS server")
+    info!(address = %addr, proxy_protocol = config.server.proxy_protocol, "listening on HTTPS");
+    match config.server.proxy_protocol {
+        true => {
+            server
+                .acceptor(tls.acceptor(ProxyProtocolAcceptor::new(&config.server.trusted_proxies)))
+                .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+        }
+
+        false => {
+            server
+                .acceptor(tls)
+                .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+        }
+    }
+    .context("failed to run HTTPS server")
 }
 
 async fn start_http_server(config: &Config, router: Router) -> eyre::Result<()> {
     let addr = config.server.to_socket_addr();
+    if config.server.proxy_protocol {
+        let handle = Handle::new();
+        tokio::spawn(shutdown_signal(Some(handle.clone())));
+
+        info!(address = ?addr, proxy_protocol = true, "listening on HTTP");
+        return axum_server::bind(addr)
+            .acceptor(ProxyProtocolAcceptor::new(&config.server.trusted_proxies))
+            .handle(handle)
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .context("failed to run HTTP server");
+    }
+
     let listener = tokio::net::TcpListener::bind(addr).await?;
     info!(address = ?addr, "listening on HTTP");
 
-    axum::serve(listener, router.into_make_service())
+    axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_signal::<SocketAddr>(None))
         .await
         .context("failed to run HTTP server")
 }
 
+/// Binds the separate listener that metrics are served on and runs it in the
+/// background.
+async fn start_metrics_server(addr: SocketAddr, path: &str) -> eyre::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to bind metrics listener on {addr}"))?;
+
+    info!(address = ?addr, path, "listening for metrics on HTTP");
+
+    let router = routes::create_metrics_router(path);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router)
+            .with_graceful_shutdown(shutdown_signal::<SocketAddr>(None))
+            .await
+        {
+            error!(error = %e, "failed to run metrics server");
+        }
+    });
+
+    Ok(())
+}
+
 async fn shutdown_signal<A>(handle: Option<Handle<A>>)
 where
     A: Address,
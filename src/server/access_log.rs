@@ -0,0 +1,340 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Writes the access log that is configured by `[server.access_log]`.
+
+use crate::{
+    config::server::access_log::{self, Format},
+    logging::RotatingFile,
+};
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    http::{HeaderMap, Method, StatusCode, Uri, Version, header},
+    response::Response,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use eyre::Context as _;
+use http_body::{Frame, SizeHint};
+use serde_json::json;
+use std::{
+    fmt::Write as _,
+    io::Write,
+    net::IpAddr,
+    pin::Pin,
+    sync::mpsc,
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Handle to the thread that writes the access log. Lines are sent to it over a
+/// channel, so that requests never wait on the file.
+#[derive(Clone)]
+pub struct AccessLog {
+    format: Format,
+    sender: mpsc::Sender<String>,
+}
+
+impl AccessLog {
+    /// Opens the access log file and starts the thread that writes to it.
+    pub fn open(config: &access_log::Config) -> eyre::Result<AccessLog> {
+        let mut file = RotatingFile::open(&config.path, config.rotation.clone())
+            .with_context(|| format!("failed to open access log `{}`", config.path.display()))?;
+
+        if config.format == Format::W3c {
+            file = file.with_header(w3c_header)?;
+        }
+
+        let (sender, receiver) = mpsc::channel::<String>();
+        thread::Builder::new()
+            .name(String::from("hazel-access-log"))
+            .spawn(move || {
+                for line in receiver {
+                    if let Err(e) = file.write_all(line.as_bytes()) {
+                        error!(error = %e, "failed to write to access log");
+                    }
+                }
+            })?;
+
+        Ok(AccessLog {
+            format: config.format,
+            sender,
+        })
+    }
+
+    /// Starts an [`Entry`] for a request that was just received.
+    pub fn entry(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        version: Version,
+        headers: &HeaderMap,
+        client_ip: Option<IpAddr>,
+        request_id: &str,
+    ) -> Entry {
+        let header = |name| {
+            headers
+                .get(name)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        };
+
+        Entry {
+            log: self.clone(),
+            time: Utc::now(),
+            start: Instant::now(),
+            client_ip,
+            method: method.clone(),
+            uri: uri.clone(),
+            version,
+            referer: header(header::REFERER),
+            user_agent: header(header::USER_AGENT),
+            request_id: request_id.to_owned(),
+            status: StatusCode::OK,
+            bytes: 0,
+        }
+    }
+}
+
+/// A request in the access log. It's written once its response body has been sent, or
+/// the client went away, so that the bytes that were sent and the latency include the
+/// whole body.
+pub struct Entry {
+    log: AccessLog,
+    time: DateTime<Utc>,
+    start: Instant,
+    client_ip: Option<IpAddr>,
+    method: Method,
+    uri: Uri,
+    version: Version,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    request_id: String,
+    status: StatusCode,
+    bytes: u64,
+}
+
+impl Entry {
+    /// Wraps the body of `response`, so that this entry is written once it has been
+    /// sent.
+    pub fn finish(mut self, response: Response<Body>) -> Response<Body> {
+        self.status = response.status();
+
+        let (parts, body) = response.into_parts();
+        Response::from_parts(
+            parts,
+            Body::new(Counted {
+                inner: body,
+                entry: self,
+            }),
+        )
+    }
+
+    fn format(&self, latency: Duration) -> String {
+        match self.log.format {
+            Format::Common => self.common(),
+            Format::Combined => {
+                let mut line = self.common();
+                line.pop();
+
+                write!(
+                    line,
+                    " \"{}\" \"{}\" \"{}\" {:.3}",
+                    escape(self.referer.as_deref().unwrap_or("-")),
+                    escape(self.user_agent.as_deref().unwrap_or("-")),
+                    escape(&self.request_id),
+                    latency.as_secs_f64() * 1000.0,
+                )
+                .unwrap();
+
+                line.push('\n');
+                line
+            }
+
+            Format::W3c => {
+                let field = |value: Option<&str>| match value {
+                    Some(value) if !value.is_empty() => value
+                        .chars()
+                        .map(|c| if c.is_whitespace() || c.is_control() { '+' } else { c })
+                        .collect(),
+                    _ => String::from("-"),
+                };
+
+                format!(
+                    "{} {} {} {} {} {} {} {:.3} {:?} {} {} {}\n",
+                    self.time.format("%Y-%m-%d %H:%M:%S"),
+                    field(self.client_ip.map(|ip| ip.to_string()).as_deref()),
+                    self.method,
+                    field(Some(self.uri.path())),
+                    field(self.uri.query()),
+                    self.status.as_u16(),
+                    self.bytes,
+                    latency.as_secs_f64(),
+                    self.version,
+                    field(self.user_agent.as_deref()),
+                    field(self.referer.as_deref()),
+                    field(Some(&self.request_id)),
+                )
+            }
+
+            Format::Json => {
+                let mut line = json!({
+                    "time": self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                    "client_ip": self.client_ip,
+                    "method": self.method.as_str(),
+                    "path": self.uri.path(),
+                    "query": self.uri.query(),
+                    "protocol": format!("{:?}", self.version),
+                    "status": self.status.as_u16(),
+                    "bytes": self.bytes,
+                    "referer": self.referer,
+                    "user_agent": self.user_agent,
+                    "request_id": self.request_id,
+                    "latency_ms": latency.as_micros() as f64 / 1000.0,
+                })
+                .to_string();
+
+                line.push('\n');
+                line
+            }
+        }
+    }
+
+    /// Formats this entry in the Common Log Format.
+    fn common(&self) -> String {
+        let bytes = match self.bytes {
+            0 => String::from("-"),
+            bytes => bytes.to_string(),
+        };
+
+        format!(
+            "{} - - [{}] \"{} {} {:?}\" {} {}\n",
+            self.client_ip.map_or_else(|| String::from("-"), |ip| ip.to_string()),
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.method,
+            escape(self.uri.path_and_query().map_or("/", |pq| pq.as_str())),
+            self.version,
+            self.status.as_u16(),
+            bytes,
+        )
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        let line = self.format(self.start.elapsed());
+
+        // the writer thread only goes away if it panicked
+        let _ = self.log.sender.send(line);
+    }
+}
+
+/// A response body that counts the bytes that were sent for its [`Entry`].
+struct Counted {
+    inner: Body,
+    entry: Entry,
+}
+
+impl HttpBody for Counted {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(ref frame))) = poll &&
+            let Some(data) = frame.data_ref()
+        {
+            this.entry.bytes += data.len() as u64;
+        }
+
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+fn w3c_header() -> String {
+    format!(
+        "#Version: 1.0\n#Software: Hazel {}\n#Date: {}\n#Fields: date time c-ip cs-method cs-uri-stem cs-uri-query sc-status sc-bytes time-taken cs-version cs(User-Agent) cs(Referer) x-request-id\n",
+        crate::version(),
+        Utc::now().format("%Y-%m-%d %H:%M:%S"),
+    )
+}
+
+/// Escapes `value` to be put in a quoted field, like Apache does: `"` and `\` are
+/// backslash-escaped, and other non-printable characters are written as `\xhh`.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => write!(escaped, "\\x{byte:02x}").unwrap(),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccessLog, Entry};
+    use crate::config::server::access_log::Format;
+    use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, Uri, Version, header};
+    use chrono::{TimeZone, Utc};
+    use std::{sync::mpsc, time::Duration};
+
+    fn entry(format: Format) -> Entry {
+        let (sender, _) = mpsc::channel();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::USER_AGENT, HeaderValue::from_static("curl/8.5.0 \"quoted\""));
+
+        let mut entry = AccessLog { format, sender }.entry(
+            &Method::GET,
+            &Uri::from_static("/releases/hazel.tar.gz?v=2"),
+            Version::HTTP_11,
+            &headers,
+            Some("203.0.113.7".parse().unwrap()),
+            "Xb2kQ9aLmP0z",
+        );
+
+        entry.time = Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap();
+        entry.status = StatusCode::PARTIAL_CONTENT;
+        entry.bytes = 1024;
+        entry
+    }
+
+    #[test]
+    fn test_format() {
+        let latency = Duration::from_micros(12_345);
+
+        assert_eq!(
+            entry(Format::Combined).format(latency),
+            "203.0.113.7 - - [02/Jan/2025:03:04:05 +0000] \"GET /releases/hazel.tar.gz?v=2 HTTP/1.1\" 206 1024 \"-\" \"curl/8.5.0 \\\"quoted\\\"\" \"Xb2kQ9aLmP0z\" 12.345\n"
+        );
+
+        assert_eq!(
+            entry(Format::W3c).format(latency),
+            "2025-01-02 03:04:05 203.0.113.7 GET /releases/hazel.tar.gz v=2 206 1024 0.012 HTTP/1.1 curl/8.5.0+\"quoted\" - Xb2kQ9aLmP0z\n"
+        );
+    }
+}
@@ -0,0 +1,390 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Renders the contents of directories when `[server.autoindex]` is enabled.
+
+use super::{
+    canonical::{CanonicalPath, SEGMENT},
+    negotiate,
+};
+use crate::{
+    config::Config,
+    storage::{DirEntry, Listing, Storage},
+};
+use axum::{
+    Json,
+    body::Body,
+    http::{HeaderMap, StatusCode, Uri, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use percent_encoding::utf8_percent_encode;
+use serde_json::json;
+use std::{fmt::Write, time::SystemTime};
+
+/// What the entries in a listing are sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    Name,
+    Size,
+    Modified,
+}
+
+impl Sort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Sort::Name => "name",
+            Sort::Size => "size",
+            Sort::Modified => "modified",
+        }
+    }
+}
+
+/// Options for a listing that are given in the query string, like
+/// `?sort=size&order=desc&cursor=...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Params {
+    pub sort: Sort,
+    pub descending: bool,
+    pub cursor: Option<String>,
+}
+
+impl Params {
+    /// Parses the parameters from a query string. Unknown parameters and values are
+    /// ignored.
+    pub fn parse(query: Option<&str>) -> Params {
+        let mut params = Params {
+            sort: Sort::Name,
+            descending: false,
+            cursor: None,
+        };
+
+        for (key, value) in url::form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+            match (&*key, &*value) {
+                ("sort", "name") => params.sort = Sort::Name,
+                ("sort", "size") => params.sort = Sort::Size,
+                ("sort", "modified") => params.sort = Sort::Modified,
+                ("order", "asc") => params.descending = false,
+                ("order", "desc") => params.descending = true,
+                ("cursor", cursor) if !cursor.is_empty() => params.cursor = Some(cursor.to_owned()),
+                _ => {}
+            }
+        }
+
+        params
+    }
+
+    fn to_query(&self, sort: Sort, descending: bool, cursor: Option<&str>) -> String {
+        let mut serializer = url::form_urlencoded::Serializer::for_suffix(String::from("?"), 1);
+        serializer
+            .append_pair("sort", sort.as_str())
+            .append_pair("order", if descending { "desc" } else { "asc" });
+
+        if let Some(cursor) = cursor {
+            serializer.append_pair("cursor", cursor);
+        }
+
+        serializer.finish()
+    }
+}
+
+/// Lists `directory` (the request's path in the storage backend) if `[server.autoindex]`
+/// allows it. This is called once the path didn't resolve to a file.
+///
+/// If the path doesn't end with a slash but is a directory, then the client is
+/// redirected to the path with one so relative links in the listing resolve correctly.
+/// `None` is returned if the directory can't be listed, so the caller can send its own
+/// response.
+///
+/// Pages of a listing are cut by name, so it is only sorted by the `sort` and `order`
+/// parameters when it fits in a single page.
+pub async fn serve(
+    storage: &Storage,
+    config: &Config,
+    directory: &str,
+    path: &CanonicalPath,
+    uri: &Uri,
+    headers: &HeaderMap,
+) -> Option<Response<Body>> {
+    let autoindex = config.server.autoindex.as_ref()?;
+    if !autoindex.is_enabled_for(path) {
+        return None;
+    }
+
+    if !path.is_directory() {
+        return match storage.list(directory, None, 1).await {
+            Ok(Some(_)) => Some(redirect_to_directory(path, uri)),
+
+            _ => None,
+        };
+    }
+
+    let path: &str = path;
+    let mut params = Params::parse(uri.query());
+    let mut listing = match storage
+        .list(directory, params.cursor.as_deref(), autoindex.page_size)
+        .await
+    {
+        Ok(Some(listing)) => listing,
+        Ok(None) => return None,
+        Err(e) => {
+            error!(error = %e, path, "unable to list directory");
+            sentry::capture_error(&e);
+
+            return Some(
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({
+                        "status": "failed",
+                        "message": "unable to list directory! try again later maybe?",
+                        "context": {
+                            "query": path
+                        }
+                    })),
+                )
+                    .into_response(),
+            );
+        }
+    };
+
+    if params.cursor.is_some() || listing.next.is_some() {
+        params.sort = Sort::Name;
+        params.descending = false;
+    } else {
+        sort(&mut listing.entries, params.sort, params.descending);
+    }
+
+    let response = match negotiate::preferred(headers, &["text/html", "application/json"]) {
+        Some("application/json") => Json(render_json(path, &listing)).into_response(),
+        _ => (
+            [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+            render_html(path, &listing, &params),
+        )
+            .into_response(),
+    };
+
+    Some(([(header::VARY, "accept")], response).into_response())
+}
+
+/// Redirects to the request's canonical path with a trailing slash, keeping the query
+/// string. The location always starts with a single slash, so it can't be mistaken for
+/// a protocol-relative URL.
+pub fn redirect_to_directory(path: &CanonicalPath, uri: &Uri) -> Response<Body> {
+    let location = match uri.query() {
+        Some(query) => format!("{}/?{query}", path.encode()),
+        None => format!("{}/", path.encode()),
+    };
+
+    Redirect::permanent(&location).into_response()
+}
+
+/// Sorts the entries of a listing, always keeping directories before files.
+pub fn sort(entries: &mut [DirEntry], sort: Sort, descending: bool) {
+    entries.sort_by(|a, b| {
+        let ordering = match sort {
+            Sort::Name => a.name.cmp(&b.name),
+            Sort::Size => a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name)),
+            Sort::Modified => a.last_modified.cmp(&b.last_modified).then_with(|| a.name.cmp(&b.name)),
+        };
+
+        b.is_dir.cmp(&a.is_dir).then(match descending {
+            true => ordering.reverse(),
+            false => ordering,
+        })
+    });
+}
+
+fn render_json(path: &str, listing: &Listing) -> serde_json::Value {
+    json!({
+        "path": path,
+        "entries": listing.entries.iter().map(|entry| json!({
+            "name": entry.name,
+            "type": if entry.is_dir { "directory" } else { "file" },
+            "size": entry.size,
+            "last_modified": entry.last_modified.map(|time| {
+                DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+            }),
+        })).collect::<Vec<_>>(),
+        "next_cursor": listing.next,
+    })
+}
+
+fn render_html(path: &str, listing: &Listing, params: &Params) -> String {
+    let title = format!("Index of {}", escape(path));
+    let mut html = String::new();
+
+    write!(
+        html,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2rem; }}
+table {{ border-collapse: collapse; }}
+th, td {{ padding: 0.25rem 1.5rem 0.25rem 0; text-align: left; }}
+td.size {{ text-align: right; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<table>
+<thead><tr>"#
+    )
+    .unwrap();
+
+    let paginated = params.cursor.is_some() || listing.next.is_some();
+    for (sort, label) in [(Sort::Name, "Name"), (Sort::Size, "Size"), (Sort::Modified, "Last Modified")] {
+        // a paginated listing can only be sorted by name, in the order of its pages
+        if paginated {
+            write!(html, "<th>{label}</th>").unwrap();
+            continue;
+        }
+
+        // clicking on the column that is already sorted flips the order
+        let descending = sort == params.sort && !params.descending;
+        let marker = match (sort == params.sort, params.descending) {
+            (true, false) => " ↑",
+            (true, true) => " ↓",
+            (false, _) => "",
+        };
+
+        write!(
+            html,
+            r#"<th><a href="{}">{label}</a>{marker}</th>"#,
+            escape(&params.to_query(sort, descending, None))
+        )
+        .unwrap();
+    }
+
+    html.push_str("</tr></thead>\n<tbody>\n");
+    if path != "/" {
+        html.push_str("<tr><td><a href=\"../\">../</a></td><td class=\"size\">-</td><td>-</td></tr>\n");
+    }
+
+    for entry in &listing.entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        writeln!(
+            html,
+            r#"<tr><td><a href="./{}{suffix}">{}{suffix}</a></td><td class="size"{}>{}</td><td>{}</td></tr>"#,
+            utf8_percent_encode(&entry.name, SEGMENT),
+            escape(&entry.name),
+            match entry.size {
+                Some(size) => format!(r#" title="{size} bytes""#),
+                None => String::new(),
+            },
+            entry.size.map(humanize).unwrap_or_else(|| String::from("-")),
+            entry
+                .last_modified
+                .map(format_time)
+                .unwrap_or_else(|| String::from("-")),
+        )
+        .unwrap();
+    }
+
+    html.push_str("</tbody>\n</table>\n");
+    if let Some(ref next) = listing.next {
+        writeln!(
+            html,
+            r#"<p><a href="{}">Next page →</a></p>"#,
+            escape(&params.to_query(params.sort, params.descending, Some(next)))
+        )
+        .unwrap();
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+pub fn escape(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for ch in input.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            ch => escaped.push(ch),
+        }
+    }
+
+    escaped
+}
+
+fn humanize(size: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Params, Sort, escape, humanize, sort};
+    use crate::storage::DirEntry;
+
+    fn entry(name: &str, is_dir: bool, size: Option<u64>) -> DirEntry {
+        DirEntry {
+            name: name.to_owned(),
+            is_dir,
+            size,
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn test_params() {
+        let params = Params::parse(Some("sort=size&order=desc&cursor=a%2Fb&unknown=1"));
+        assert_eq!(params.sort, Sort::Size);
+        assert!(params.descending);
+        assert_eq!(params.cursor.as_deref(), Some("a/b"));
+
+        assert_eq!(Params::parse(Some("sort=nope")).sort, Sort::Name);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut entries =
+            vec![entry("b.txt", false, Some(1)), entry("z", true, None), entry("a.txt", false, Some(10))];
+
+        sort(&mut entries, Sort::Size, true);
+        let names = entries.iter().map(|e| e.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["z", "a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn test_render_helpers() {
+        assert_eq!(escape(r#"<a href="x">"#), "&lt;a href=&quot;x&quot;&gt;");
+        assert_eq!(humanize(512), "512 B");
+        assert_eq!(humanize(1536), "1.5 KiB");
+    }
+}
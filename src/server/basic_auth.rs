@@ -0,0 +1,267 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP Basic authentication against the htpasswd files of `[[server.basic_auth]]`.
+
+use crate::config::server::basic_auth;
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use axum::http::HeaderMap;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use eyre::Context;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+/// The realms that were loaded from `[[server.basic_auth]]`.
+#[derive(Clone, Default)]
+pub struct Realms(Arc<Vec<Realm>>);
+
+/// A realm and the users that were loaded from its htpasswd file.
+pub struct Realm {
+    config: basic_auth::Realm,
+    state: RwLock<State>,
+}
+
+#[derive(Default)]
+struct State {
+    modified: Option<SystemTime>,
+    users: HashMap<String, String>,
+
+    /// SHA-256 digests of the passwords that were verified against `users`, so that
+    /// the (intentionally slow) password hash isn't computed on every request.
+    verified: HashMap<String, [u8; 32]>,
+}
+
+impl Realms {
+    /// Loads the htpasswd file of every realm, failing if any of them can't be read.
+    pub fn load(realms: &[basic_auth::Realm]) -> eyre::Result<Realms> {
+        let realms = realms
+            .iter()
+            .map(|config| {
+                let (modified, users) = read(&config.htpasswd)?;
+                Ok(Realm {
+                    config: config.clone(),
+                    state: RwLock::new(State {
+                        modified,
+                        users,
+                        verified: HashMap::new(),
+                    }),
+                })
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(Realms(Arc::new(realms)))
+    }
+
+    /// Returns the first realm that protects `path`.
+    pub fn find(&self, path: &str) -> Option<&Realm> {
+        self.0.iter().find(|realm| realm.config.is_required_for(path))
+    }
+}
+
+impl Realm {
+    /// Name of the realm, as sent in the `WWW-Authenticate` challenge.
+    pub fn name(&self) -> &str {
+        &self.config.realm
+    }
+
+    /// Checks the credentials in the `Authorization` header against the realm's users,
+    /// reloading the htpasswd file first if it has changed. Returns the name of the
+    /// user if they're valid.
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Option<String> {
+        let (user, password) = credentials(headers)?;
+
+        self.reload().await;
+
+        let digest: [u8; 32] = Sha256::digest(password.as_bytes()).into();
+        let (hash, exists) = {
+            let state = self.state.read().unwrap();
+            if state.verified.get(&user) == Some(&digest) {
+                return Some(user);
+            }
+
+            match state.users.get(&user) {
+                Some(hash) => (hash.clone(), true),
+
+                // the password of a user that doesn't exist is still checked against the hash
+                // of one that does, so how long it takes doesn't give away which users exist
+                None => (state.users.values().next()?.clone(), false),
+            }
+        };
+
+        let verified = tokio::task::spawn_blocking(move || verify(&password, &hash))
+            .await
+            .unwrap_or(false);
+
+        if !(verified && exists) {
+            return None;
+        }
+
+        self.state.write().unwrap().verified.insert(user.clone(), digest);
+        Some(user)
+    }
+
+    async fn reload(&self) {
+        let path = self.config.htpasswd.clone();
+        let modified = tokio::fs::metadata(&path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified == self.state.read().unwrap().modified {
+            return;
+        }
+
+        match tokio::task::spawn_blocking(move || read(&path)).await {
+            Ok(Ok((modified, users))) => {
+                info!(realm = self.name(), users = users.len(), "reloaded htpasswd file");
+                *self.state.write().unwrap() = State {
+                    modified,
+                    users,
+                    verified: HashMap::new(),
+                };
+            }
+
+            Ok(Err(e)) => {
+                warn!(realm = self.name(), error = %e, "failed to reload htpasswd file, keeping the previous users")
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+fn read(path: &Path) -> eyre::Result<(Option<SystemTime>, HashMap<String, String>)> {
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read htpasswd file {}", path.display()))?;
+
+    Ok((modified, parse(&contents)))
+}
+
+fn parse(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let Some((user, hash)) = line.split_once(':') else {
+                warn!("skipping malformed htpasswd line");
+                return None;
+            };
+
+            if !(hash.starts_with("$2") || hash.starts_with("$argon2")) {
+                warn!(
+                    user,
+                    "skipping htpasswd user with an unsupported hash; only bcrypt and argon2 are supported"
+                );
+                return None;
+            }
+
+            Some((user.to_owned(), hash.to_owned()))
+        })
+        .collect()
+}
+
+fn verify(password: &str, hash: &str) -> bool {
+    match hash.starts_with("$argon2") {
+        true => PasswordHash::new(hash)
+            .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok()),
+
+        false => bcrypt::verify(password, hash).unwrap_or(false),
+    }
+}
+
+fn credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let (scheme, encoded) = headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .split_once(' ')?;
+
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+
+    Some((user.to_owned(), password.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        use argon2::{Algorithm, Params, PasswordHasher, Version, password_hash::SaltString};
+
+        let salt = SaltString::from_b64("c2FsdHNhbHRzYWx0").unwrap();
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::new(8, 1, 1, None).unwrap())
+            .hash_password(b"hunter2", &salt)
+            .unwrap();
+        let bcrypt = bcrypt::hash_with_result("hunter2", 4)
+            .unwrap()
+            .format_for_version(bcrypt::Version::TwoY);
+
+        let users = parse(&format!(
+            "# comment\nalice:{bcrypt}\nbob:$apr1$abc$def\n\ncarol:{argon2}\nmalformed\n"
+        ));
+
+        assert_eq!(users.len(), 2);
+        assert!(!users.contains_key("bob"));
+
+        assert!(verify("hunter2", &users["alice"]));
+        assert!(!verify("hunter3", &users["alice"]));
+        assert!(verify("hunter2", &users["carol"]));
+        assert!(!verify("hunter3", &users["carol"]));
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let htpasswd = std::env::temp_dir().join(format!("hazel-htpasswd-{}", std::process::id()));
+        let hash = bcrypt::hash("hunter2", 4).unwrap();
+        fs::write(&htpasswd, format!("alice:{hash}\n")).unwrap();
+
+        let realms = Realms::load(&[basic_auth::Realm {
+            realm: String::from("hazel"),
+            prefixes: vec![String::from("/")],
+            htpasswd: htpasswd.clone(),
+        }])
+        .unwrap();
+
+        let realm = realms.find("/a.txt").unwrap();
+        let authenticate = |credentials: &str| {
+            let mut headers = HeaderMap::new();
+            let value = format!("Basic {}", STANDARD.encode(credentials));
+            headers.insert(axum::http::header::AUTHORIZATION, value.parse().unwrap());
+
+            crate::server::testing::runtime().block_on(realm.authenticate(&headers))
+        };
+
+        assert_eq!(authenticate("alice:hunter2").as_deref(), Some("alice"));
+        assert_eq!(authenticate("alice:hunter2").as_deref(), Some("alice"));
+        assert_eq!(authenticate("alice:hunter3"), None);
+
+        // the password is checked against alice's hash, but it's still not alice
+        assert_eq!(authenticate("mallory:hunter2"), None);
+
+        fs::remove_file(htpasswd).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The canonical form of a request's path, which every check and handler works with so
+//! that the same object can't be reached through a spelling of its path that a check
+//! doesn't recognise, like `/%70rivate/a` or `//private/a` for `/private/a`.
+
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use std::{fmt::Display, ops::Deref};
+
+/// Characters that need to be percent-encoded in a path segment.
+pub const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// A percent-decoded path with its repeated slashes collapsed and its `.` segments
+/// removed. It always starts with a single `/`, and ends with one if the path that it
+/// was parsed from did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalPath(String);
+
+/// Reasons why a path doesn't have a canonical form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A segment isn't valid UTF-8 or contains a control character (like a NUL byte or
+    /// a newline) once decoded.
+    Malformed,
+
+    /// A segment is `..`.
+    Traversal,
+
+    /// A segment contains an encoded `/`, which would change where the segments are
+    /// split once decoded.
+    EncodedSlash,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Malformed => f.write_str("path isn't valid utf-8 or contains control characters"),
+            Error::Traversal => f.write_str("path can't contain `..` segments"),
+            Error::EncodedSlash => f.write_str("path can't contain encoded slashes"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl CanonicalPath {
+    /// Parses the path of a request, as it was sent.
+    pub fn parse(path: &str) -> Result<CanonicalPath, Error> {
+        let mut canonical = String::with_capacity(path.len());
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            let decoded = percent_decode_str(segment)
+                .decode_utf8()
+                .map_err(|_| Error::Malformed)?;
+            match &*decoded {
+                "." => continue,
+                ".." => return Err(Error::Traversal),
+                decoded if decoded.contains('/') => return Err(Error::EncodedSlash),
+                decoded if decoded.contains(char::is_control) => return Err(Error::Malformed),
+                decoded => {
+                    canonical.push('/');
+                    canonical.push_str(decoded);
+                }
+            }
+        }
+
+        if canonical.is_empty() || path.ends_with('/') {
+            canonical.push('/');
+        }
+
+        Ok(CanonicalPath(canonical))
+    }
+
+    /// Whether if this is the path of a directory, which ends with a `/`.
+    pub fn is_directory(&self) -> bool {
+        self.0.ends_with('/')
+    }
+
+    /// Returns this path with every segment percent-encoded, for use in a URL.
+    pub fn encode(&self) -> String {
+        self.0
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl Display for CanonicalPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Deref for CanonicalPath {
+    type Target = str;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CanonicalPath, Error};
+
+    #[test]
+    fn test_parse() {
+        let parse = |path| CanonicalPath::parse(path).map(|path| path.to_string());
+
+        assert_eq!(parse("/"), Ok(String::from("/")));
+        assert_eq!(parse(""), Ok(String::from("/")));
+        assert_eq!(parse("//"), Ok(String::from("/")));
+        assert_eq!(parse("/private/a.txt"), Ok(String::from("/private/a.txt")));
+        assert_eq!(parse("//private//a.txt"), Ok(String::from("/private/a.txt")));
+        assert_eq!(parse("/%70rivate/a%20b.txt"), Ok(String::from("/private/a b.txt")));
+        assert_eq!(parse("/./private/./dir/"), Ok(String::from("/private/dir/")));
+        assert_eq!(parse("/%2570rivate"), Ok(String::from("/%70rivate")));
+
+        assert_eq!(parse("/public/../private/a.txt"), Err(Error::Traversal));
+        assert_eq!(parse("/public/%2e%2E/private/a.txt"), Err(Error::Traversal));
+        assert_eq!(parse("/public%2fa.txt"), Err(Error::EncodedSlash));
+        assert_eq!(parse("/public%2Fa.txt"), Err(Error::EncodedSlash));
+        assert_eq!(parse("/a%00.txt"), Err(Error::Malformed));
+        assert_eq!(parse("/a%0Ab.txt"), Err(Error::Malformed));
+        assert_eq!(parse("/a%7F.txt"), Err(Error::Malformed));
+        assert_eq!(parse("/a%ff.txt"), Err(Error::Malformed));
+
+        let path = CanonicalPath::parse("/private/a b+c%.txt").unwrap();
+        assert_eq!(path.encode(), "/private/a%20b%2Bc%25.txt");
+        assert!(!path.is_directory());
+    }
+}
@@ -0,0 +1,133 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! On-the-fly compression of responses, configured by `[server.compression]`.
+
+use crate::config::{
+    Config,
+    server::compression::{self, Algorithm, Level},
+};
+use axum::{
+    body::HttpBody,
+    http::{Response, StatusCode, header},
+};
+use std::sync::Arc;
+use tower_http::{
+    CompressionLevel,
+    compression::{CompressionLayer, Predicate},
+};
+
+/// Marks a response whose body is a precompressed variant of the stored object (like
+/// `app.js.br`), so it keeps the strong `ETag` of that variant.
+#[derive(Debug, Clone, Copy)]
+pub struct Precompressed;
+
+/// Creates the [`CompressionLayer`] from the `[server.compression]` table. If it isn't
+/// present, then the layer never compresses anything.
+///
+/// Responses to range requests (with a `Content-Range` header) and responses that
+/// already have a `Content-Encoding` are never compressed.
+pub fn layer(config: &Config) -> CompressionLayer<Compressible> {
+    let compression = config.server.compression.clone().map(Arc::new);
+    let enabled = |algorithm| {
+        compression
+            .as_ref()
+            .is_some_and(|compression| compression.algorithms.contains(&algorithm))
+    };
+
+    CompressionLayer::new()
+        .gzip(enabled(Algorithm::Gzip))
+        .br(enabled(Algorithm::Brotli))
+        .zstd(enabled(Algorithm::Zstd))
+        .quality(match compression.as_ref().map(|compression| compression.level) {
+            Some(Level::Fastest) => CompressionLevel::Fastest,
+            Some(Level::Best) => CompressionLevel::Best,
+            Some(Level::Precise(level)) => CompressionLevel::Precise(level),
+            Some(Level::Default) | None => CompressionLevel::Default,
+        })
+        .compress_when(Compressible(compression))
+}
+
+/// [`Predicate`] that only compresses responses whose content type is allowed by
+/// `[server.compression]` and that are large enough.
+#[derive(Clone)]
+pub struct Compressible(Option<Arc<compression::Config>>);
+
+impl Predicate for Compressible {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        let Some(ref config) = self.0 else {
+            return false;
+        };
+
+        // `multipart/byteranges` responses don't have a `Content-Range` header
+        if response.status() == StatusCode::PARTIAL_CONTENT {
+            return false;
+        }
+
+        let allowed = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| config.is_compressible(content_type));
+
+        allowed && is_large_enough(response, config.min_size)
+    }
+}
+
+/// Whether if `response` has at least `min_size` bytes, like tower-http's `SizeAbove`
+/// but without its `u16` limit. Responses whose size isn't known are large enough.
+fn is_large_enough<B>(response: &Response<B>, min_size: u64) -> bool
+where
+    B: HttpBody,
+{
+    let size = response.body().size_hint().exact().or_else(|| {
+        response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+    });
+
+    size.is_none_or(|size| size >= min_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_large_enough;
+    use axum::{
+        body::Body,
+        http::{Response, header},
+    };
+
+    #[test]
+    fn test_is_large_enough() {
+        let response = |size: usize| Response::new(Body::from(vec![0; size]));
+        assert!(is_large_enough(&response(100 * 1024), 65536 + 1024));
+        assert!(!is_large_enough(&response(64 * 1024), 65536 + 1024));
+
+        let streamed = Response::builder()
+            .header(header::CONTENT_LENGTH, "1048576")
+            .body(Body::from_stream(
+                futures_util::stream::empty::<std::io::Result<Vec<u8>>>(),
+            ))
+            .unwrap();
+
+        assert!(is_large_enough(&streamed, 1024 * 1024));
+        assert!(!is_large_enough(&streamed, 1024 * 1024 + 1));
+    }
+}
@@ -0,0 +1,262 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for [conditional requests] (RFC 9110 §13).
+//!
+//! [conditional requests]: https://www.rfc-editor.org/rfc/rfc9110#section-13
+
+use crate::storage::Metadata;
+use axum::http::{HeaderMap, Method, header};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The outcome of evaluating the preconditions of a request.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Precondition {
+    /// All preconditions passed (or there were none), so the request should be
+    /// processed as usual.
+    Passed,
+
+    /// The client's cached representation is still fresh, so a `304 Not Modified`
+    /// should be sent.
+    NotModified,
+
+    /// A precondition failed, so a `412 Precondition Failed` should be sent.
+    Failed,
+}
+
+/// Evaluates the `If-Match`, `If-Unmodified-Since`, `If-None-Match` and `If-Modified-Since`
+/// headers of a request, in the order that RFC 9110 §13.2.2 describes.
+pub fn evaluate(method: &Method, headers: &HeaderMap, metadata: &Metadata) -> Precondition {
+    let etag = metadata.etag.as_deref();
+    let last_modified = metadata.last_modified.map(truncate);
+
+    // 1. `If-Match` (strong comparison)
+    if let Some(value) = get_list(headers, header::IF_MATCH) {
+        if !matches(&value, etag, false) {
+            return Precondition::Failed;
+        }
+    } else if let Some(since) = get(headers, header::IF_UNMODIFIED_SINCE).and_then(parse_date) {
+        // 2. `If-Unmodified-Since`, only if `If-Match` is not present
+        match last_modified {
+            Some(last_modified) if last_modified <= since => {}
+            _ => return Precondition::Failed,
+        }
+    }
+
+    let is_safe = *method == Method::GET || *method == Method::HEAD;
+
+    // 3. `If-None-Match` (weak comparison)
+    if let Some(value) = get_list(headers, header::IF_NONE_MATCH) {
+        if matches(&value, etag, true) {
+            return match is_safe {
+                true => Precondition::NotModified,
+                false => Precondition::Failed,
+            };
+        }
+    } else if is_safe &&
+        let Some(since) = get(headers, header::IF_MODIFIED_SINCE).and_then(parse_date) &&
+        let Some(last_modified) = last_modified &&
+        last_modified <= since
+    {
+        // 4. `If-Modified-Since`, only if `If-None-Match` is not present
+        return Precondition::NotModified;
+    }
+
+    Precondition::Passed
+}
+
+/// Checks if `etag` (of a representation that exists) is in the list of entity tags
+/// given by a conditional header. A list of `*` matches any current representation,
+/// even one without an entity tag.
+///
+/// With a weak comparison, two entity tags match if their opaque tags are the same, even
+/// if either of them are weak. With a strong comparison, both have to be strong.
+pub fn matches(list: &str, etag: Option<&str>, weak: bool) -> bool {
+    if list.trim() == "*" {
+        return true;
+    }
+
+    let Some(etag) = etag else {
+        return false;
+    };
+
+    let (is_weak, opaque) = split_weak(etag);
+    if is_weak && !weak {
+        return false;
+    }
+
+    parse_list(list).into_iter().any(|candidate| {
+        let (candidate_weak, candidate_opaque) = split_weak(candidate);
+        candidate_opaque == opaque && (weak || !candidate_weak)
+    })
+}
+
+/// Formats a [`SystemTime`] as the value of a `Last-Modified` header.
+pub fn last_modified(time: SystemTime) -> String {
+    httpdate::fmt_http_date(time)
+}
+
+fn get(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Returns every value of a list header, joined into one list since it can be split
+/// across several header lines (RFC 9110 §5.3).
+fn get_list(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+    let values = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>();
+
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+fn parse_date(value: &str) -> Option<SystemTime> {
+    httpdate::parse_http_date(value.trim()).ok()
+}
+
+/// HTTP dates only have a resolution of seconds, so anything smaller needs to be
+/// discarded when comparing against them.
+fn truncate(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => UNIX_EPOCH + Duration::from_secs(duration.as_secs()),
+        Err(_) => time,
+    }
+}
+
+fn split_weak(etag: &str) -> (bool, &str) {
+    match etag.strip_prefix("W/") {
+        Some(opaque) => (true, opaque),
+        None => (false, etag),
+    }
+}
+
+/// Splits a list of entity tags. Entity tags can contain commas, so we can't just split
+/// on them.
+fn parse_list(list: &str) -> Vec<&str> {
+    let mut tags = Vec::new();
+    let mut rest = list.trim_start();
+
+    while !rest.is_empty() {
+        let start = match rest.strip_prefix("W/") {
+            Some(after) => rest.len() - after.len(),
+            None => 0,
+        };
+
+        if !rest[start..].starts_with('"') {
+            // not an entity tag, skip until the next element
+            match rest.find(',') {
+                Some(idx) => rest = rest[idx + 1..].trim_start(),
+                None => break,
+            }
+
+            continue;
+        }
+
+        let Some(end) = rest[start + 1..].find('"') else {
+            break;
+        };
+
+        let end = start + 1 + end + 1;
+        tags.push(&rest[..end]);
+
+        rest = rest[end..].trim_start().trim_start_matches(',').trim_start();
+    }
+
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Precondition, evaluate, matches, parse_list};
+    use crate::storage::Metadata;
+    use axum::http::{HeaderMap, HeaderValue, Method, header};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn metadata() -> Metadata {
+        Metadata {
+            content_type: String::from("text/plain"),
+            last_modified: Some(UNIX_EPOCH + Duration::from_millis(784_111_777_500)),
+            etag: Some(String::from("\"xyzzy\"")),
+            size: 12,
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(parse_list(r#""a", W/"b","c,d""#), vec![
+            r#""a""#, r#"W/"b""#, r#""c,d""#
+        ]);
+        assert_eq!(parse_list(""), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(r#""xyzzy""#, Some(r#""xyzzy""#), false));
+        assert!(matches(r#"W/"xyzzy""#, Some(r#""xyzzy""#), true));
+        assert!(!matches(r#"W/"xyzzy""#, Some(r#""xyzzy""#), false));
+        assert!(matches("*", Some(r#""xyzzy""#), false));
+        assert!(matches("*", None, false));
+        assert!(!matches(r#""xyzzy""#, None, true));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let metadata = metadata();
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(evaluate(&Method::GET, &headers, &metadata), Precondition::Passed);
+
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static(r#""abc", "xyzzy""#));
+        assert_eq!(evaluate(&Method::GET, &headers, &metadata), Precondition::NotModified);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, HeaderValue::from_static(r#""abc""#));
+        assert_eq!(evaluate(&Method::GET, &headers, &metadata), Precondition::Failed);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_MODIFIED_SINCE,
+            HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"),
+        );
+
+        assert_eq!(evaluate(&Method::GET, &headers, &metadata), Precondition::NotModified);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_UNMODIFIED_SINCE,
+            HeaderValue::from_static("Sun, 06 Nov 1994 08:49:36 GMT"),
+        );
+
+        assert_eq!(evaluate(&Method::GET, &headers, &metadata), Precondition::Failed);
+
+        // lists can be split across several header lines
+        let mut headers = HeaderMap::new();
+        headers.append(header::IF_NONE_MATCH, HeaderValue::from_static(r#""abc""#));
+        headers.append(header::IF_NONE_MATCH, HeaderValue::from_static(r#""xyzzy""#));
+        assert_eq!(evaluate(&Method::GET, &headers, &metadata), Precondition::NotModified);
+
+        // `*` matches objects that exist, even without an entity tag
+        let metadata = Metadata { etag: None, ..metadata };
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+        assert_eq!(evaluate(&Method::GET, &headers, &metadata), Precondition::NotModified);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("*"));
+        assert_eq!(evaluate(&Method::GET, &headers, &metadata), Precondition::Passed);
+    }
+}
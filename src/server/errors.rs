@@ -0,0 +1,247 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Renders error responses in the format that the client prefers.
+//!
+//! Handlers always send errors as a JSON envelope (`{"status", "message", "context"}`),
+//! which is rewritten into HTML (or a configured error document) for browsers, or
+//! into an [RFC 9457] problem details object.
+//!
+//! [RFC 9457]: https://www.rfc-editor.org/rfc/rfc9457
+
+use super::{autoindex, negotiate};
+use crate::{
+    config::{Config, server::errors::FILE_SCHEME},
+    storage::{Entry, Storage},
+};
+use axum::{
+    body::{self, Body},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    response::Response,
+};
+use serde_json::{Value, json};
+use std::path::Path;
+
+const JSON: &str = "application/json";
+const PROBLEM_JSON: &str = "application/problem+json";
+const HTML: &str = "text/html";
+
+/// Largest JSON envelope that will be rewritten. Envelopes are always tiny, so anything
+/// larger isn't one.
+const MAX_ENVELOPE_SIZE: usize = 64 * 1024;
+
+/// Rewrites the body of an error `response` into the format that the client prefers
+/// from the `Accept` header in `headers`, adding `request_id` to its context. Responses
+/// that aren't errors, or that don't have a JSON envelope as their body, are sent as-is.
+pub async fn render(
+    storage: &Storage,
+    config: &Config,
+    method: &Method,
+    path: &str,
+    headers: &HeaderMap,
+    request_id: Option<&str>,
+    response: Response<Body>,
+) -> Response<Body> {
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) || !is_envelope(&response) {
+        return response;
+    }
+
+    let available = match config.server.errors.problem_json {
+        true => [PROBLEM_JSON, JSON, HTML],
+        false => [JSON, PROBLEM_JSON, HTML],
+    };
+
+    let format = negotiate::preferred(headers, &available).unwrap_or(available[0]);
+    let (mut parts, body) = response.into_parts();
+    parts.headers.append(header::VARY, HeaderValue::from_static("accept"));
+
+    if format == JSON && request_id.is_none() {
+        return Response::from_parts(parts, body);
+    }
+
+    let bytes = body::to_bytes(body, MAX_ENVELOPE_SIZE).await.unwrap_or_default();
+    let mut envelope = serde_json::from_slice::<Value>(&bytes).unwrap_or_default();
+    if let Some(id) = request_id &&
+        let Some(envelope) = envelope.as_object_mut() &&
+        let Some(context) = envelope.entry("context").or_insert_with(|| json!({})).as_object_mut()
+    {
+        context.insert(String::from("request_id"), Value::from(id));
+    }
+
+    if format == JSON {
+        // the body of a `HEAD` response is empty, so there's nothing to add the request
+        // id to
+        if !envelope.is_object() {
+            return Response::from_parts(parts, Body::from(bytes));
+        }
+
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::from(envelope.to_string()));
+    }
+
+    let title = status.canonical_reason().unwrap_or("Error");
+    let message = envelope.get("message").and_then(Value::as_str).unwrap_or(title);
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    if format == PROBLEM_JSON {
+        let mut problem = json!({
+            "type": "about:blank",
+            "title": title,
+            "status": status.as_u16(),
+            "detail": message,
+            "instance": path,
+        });
+
+        if let Some(code) = envelope.get("status") {
+            problem["code"] = code.clone();
+        }
+
+        if let Some(context) = envelope.get("context") {
+            problem["context"] = context.clone();
+        }
+
+        parts
+            .headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+
+        return Response::from_parts(parts, Body::from(problem.to_string()));
+    }
+
+    // the body of a `HEAD` response is never sent, so there's no need to load the document
+    if *method != Method::HEAD &&
+        let Some(location) = config.server.errors.page_for(status.as_u16())
+    {
+        match load_page(storage, location).await {
+            Some((content_type, body)) => {
+                if let Ok(value) = HeaderValue::from_str(&content_type) {
+                    parts.headers.insert(header::CONTENT_TYPE, value);
+                }
+
+                return Response::from_parts(parts, body);
+            }
+
+            None => warn!(location, %status, "unable to load error document, using the default one"),
+        }
+    }
+
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+
+    Response::from_parts(parts, Body::from(default_page(status, message, request_id)))
+}
+
+fn is_envelope(response: &Response<Body>) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with(JSON))
+}
+
+/// Loads the error document at `location` from the storage backend, or from the local
+/// filesystem if it starts with `file://`.
+async fn load_page(storage: &Storage, location: &str) -> Option<(String, Body)> {
+    if let Some(path) = location.strip_prefix(FILE_SCHEME) {
+        let contents = tokio::fs::read(path)
+            .await
+            .inspect_err(|e| error!(error = %e, path, "unable to read error document"))
+            .ok()?;
+
+        let content_type = mime_guess::from_path(Path::new(path))
+            .first_raw()
+            .unwrap_or("text/html; charset=utf-8");
+
+        return Some((content_type.to_owned(), Body::from(contents)));
+    }
+
+    let Ok(Some(Entry::File(metadata))) = storage.stat(location).await else {
+        return None;
+    };
+
+    let stream = storage
+        .read(location, None)
+        .await
+        .inspect_err(|e| error!(error = %e, location, "unable to read error document"))
+        .ok()??;
+
+    Some((metadata.content_type, Body::from_stream(stream)))
+}
+
+fn default_page(status: StatusCode, message: &str, request_id: Option<&str>) -> String {
+    let title = format!(
+        "{} {}",
+        status.as_u16(),
+        autoindex::escape(status.canonical_reason().unwrap_or("Error"))
+    );
+
+    let footer = match request_id {
+        Some(id) => format!("Noelware/hazel &middot; request <code>{}</code>", autoindex::escape(id)),
+        None => String::from("Noelware/hazel"),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>body {{ font-family: system-ui, sans-serif; margin: 2rem; }}</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>{}</p>
+<hr>
+<small>{footer}</small>
+</body>
+</html>
+"#,
+        autoindex::escape(message)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{router, send, text};
+    use axum::http::StatusCode;
+    use std::fs;
+
+    #[test]
+    fn test_mount_error_documents() {
+        let (dir, router) = router(
+            "mount-error-documents",
+            "[server.errors.pages]\n404 = \"missing.html\"\n\n[mounts.\"/docs\".storage.filesystem]\ndirectory = \"$DIR/public\"",
+        );
+
+        fs::write(dir.join("missing.html"), "top-level").unwrap();
+        fs::write(dir.join("public/missing.html"), "mount").unwrap();
+
+        let body = |path: &str| {
+            let response = send(&router, path, &[("accept", "text/html")]);
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{path}");
+
+            text(response)
+        };
+
+        assert_eq!(body("/docs/nope.txt"), "mount");
+        assert_eq!(body("//docs/nope.txt"), "mount");
+        assert_eq!(body("/nope.txt"), "top-level");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
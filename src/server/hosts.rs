@@ -0,0 +1,194 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Virtual hosts from `[hosts]` that are picked by the `Host` header of a request.
+
+use super::mounts::Mounts;
+use crate::{
+    config::Config,
+    storage::{Caches, Storage},
+};
+use axum::http::{HeaderMap, Uri, header, uri::Authority};
+use eyre::Context;
+use std::sync::Arc;
+
+/// The virtual hosts of `[hosts]`, with exact names ordered before wildcards and
+/// wildcards ordered from the longest suffix to the shortest, so that the most specific
+/// one that a host matches is picked.
+#[derive(Clone, Default)]
+pub struct Hosts(Arc<[Host]>);
+
+/// A virtual host.
+pub struct Host {
+    /// The name, or the suffix that every subdomain ends with (like `.example.com`)
+    /// if it's a wildcard.
+    name: String,
+    wildcard: bool,
+
+    /// The storage backend that the host's objects are served from.
+    pub storage: Storage,
+
+    /// The configuration with the settings that this host overrides applied to it.
+    pub config: Config,
+
+    /// The host's own mounts.
+    pub mounts: Mounts,
+}
+
+impl Hosts {
+    /// Creates the storage backends of the virtual hosts in `config`, which are put
+    /// behind `caches`.
+    pub fn new(config: &Config, caches: &Caches) -> eyre::Result<Hosts> {
+        let mut hosts = Vec::with_capacity(config.hosts.len());
+        for (name, host) in &config.hosts {
+            let normalized = normalize(name);
+            let (suffix, wildcard) = match normalized.strip_prefix('*') {
+                Some(suffix) if suffix.starts_with('.') && suffix.len() > 1 => (suffix.to_owned(), true),
+                Some(_) => {
+                    bail!("host `{name}` can only have a wildcard as its first label, like `*.example.com`")
+                }
+                None => (normalized, false),
+            };
+
+            if suffix.is_empty() || suffix.contains(['*', '/', ':']) {
+                bail!("host `{name}` is not a valid host name");
+            }
+
+            let mut effective = Config {
+                storage: host.storage.clone(),
+                mounts: host.mounts.clone(),
+                hosts: Default::default(),
+                ..config.clone()
+            };
+
+            let storage = caches.attach(
+                Storage::new(host.storage.clone())
+                    .with_context(|| format!("failed to create the storage of host `{name}`"))?,
+            );
+
+            host.apply(&mut effective.server);
+            hosts.push(Host {
+                name: suffix,
+                wildcard,
+                storage,
+                mounts: Mounts::new(&effective, caches)
+                    .with_context(|| format!("failed to create the mounts of host `{name}`"))?,
+                config: effective,
+            });
+        }
+
+        hosts.sort_by(|a, b| a.wildcard.cmp(&b.wildcard).then(b.name.len().cmp(&a.name.len())));
+        Ok(Hosts(hosts.into()))
+    }
+
+    /// Initializes the storage backends of every virtual host and their mounts.
+    pub async fn init(&self) -> eyre::Result<()> {
+        for host in self.0.iter() {
+            host.storage
+                .init()
+                .await
+                .with_context(|| format!("failed to initialize the storage of host `{}`", host.display()))?;
+
+            host.mounts.init().await?;
+            info!(host = %host.display(), backend = host.storage.backend_name(), "serving virtual host");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the virtual host that a request for `uri` with `headers` is for, from the
+    /// authority of the URI (HTTP/2's `:authority`) or the `Host` header.
+    pub fn for_request(&self, uri: &Uri, headers: &HeaderMap) -> Option<&Host> {
+        match uri.host() {
+            Some(host) => self.resolve(host),
+            None => {
+                let authority = headers
+                    .get(header::HOST)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<Authority>().ok())?;
+
+                self.resolve(authority.host())
+            }
+        }
+    }
+
+    /// Returns the virtual host that `host` (without a port) matches, or `None` if it
+    /// doesn't match any.
+    pub fn resolve(&self, host: &str) -> Option<&Host> {
+        let host = normalize(host);
+        self.0.iter().find(|candidate| match candidate.wildcard {
+            true => host.ends_with(&candidate.name),
+            false => host == candidate.name,
+        })
+    }
+}
+
+impl Host {
+    fn display(&self) -> String {
+        match self.wildcard {
+            true => format!("*{}", self.name),
+            false => self.name.clone(),
+        }
+    }
+}
+
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hosts;
+    use crate::{config::Config, storage::Caches};
+
+    #[test]
+    fn test_resolve() {
+        let config: Config = toml::from_str(
+            r#"
+            [hosts."cdn.example.com"]
+            headers = { cache-control = "public, max-age=86400" }
+            storage.filesystem.directory = "./cdn"
+
+            [hosts."*.example.com"]
+            storage.filesystem.directory = "./wildcard"
+
+            [hosts."*.docs.example.com"]
+            index_files = ["index.html"]
+            storage.filesystem.directory = "./docs"
+            "#,
+        )
+        .unwrap();
+
+        let hosts = Hosts::new(&config, &Caches::default()).unwrap();
+        let host = hosts.resolve("CDN.example.com.").unwrap();
+        assert_eq!(host.config.server.headers["cache-control"], "public, max-age=86400");
+
+        let host = hosts.resolve("v1.docs.example.com").unwrap();
+        assert_eq!(host.config.server.index_files, ["index.html"]);
+
+        let host = hosts.resolve("dl.example.com").unwrap();
+        assert!(host.config.server.index_files.is_empty());
+
+        assert!(hosts.resolve("example.com").is_none());
+        assert!(hosts.resolve("example.org").is_none());
+
+        let headers = [(axum::http::header::HOST, "cdn.example.com:8989".parse().unwrap())]
+            .into_iter()
+            .collect();
+
+        let host = hosts.for_request(&"/hazel.tar.gz".parse().unwrap(), &headers).unwrap();
+        assert_eq!(host.name, "cdn.example.com");
+    }
+}
@@ -0,0 +1,283 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verifies the bearer tokens that are required by `[server.jwt]`.
+
+use crate::config::{server::jwt, util};
+use eyre::Context;
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation,
+    errors::ErrorKind,
+    jwk::{AlgorithmParameters, JwkSet, KeyAlgorithm},
+};
+use serde_json::{Map, Value};
+use std::{fmt::Display, fs, sync::Arc};
+
+/// Verifies tokens against the keys that were loaded from `[server.jwt]`.
+#[derive(Clone)]
+pub struct Verifier(Arc<Inner>);
+
+struct Inner {
+    config: jwt::Config,
+    keys: Vec<Key>,
+}
+
+struct Key {
+    id: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey,
+}
+
+/// Why a token was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The token is malformed, has an invalid signature, or failed one of the claim checks.
+    Invalid(String),
+
+    /// The token is valid, but doesn't grant access to the requested path.
+    Forbidden,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Invalid(reason) => write!(f, "bearer token is invalid: {reason}"),
+            Error::Forbidden => f.write_str("bearer token doesn't grant access to this object"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Verifier {
+    /// Loads the keys from `config`, reading any public keys and the JWKS from disk.
+    pub fn load(config: &jwt::Config) -> eyre::Result<Verifier> {
+        let mut keys = Vec::with_capacity(config.keys.len());
+        for key in &config.keys {
+            let decoding = match (key.algorithm, &key.secret, &key.public_key) {
+                (jwt::Algorithm::HS256, Some(secret), None) => DecodingKey::from_secret(secret.as_bytes()),
+                (jwt::Algorithm::RS256 | jwt::Algorithm::EdDSA, None, Some(path)) => {
+                    let pem =
+                        fs::read(path).with_context(|| format!("failed to read public key {}", path.display()))?;
+
+                    match key.algorithm {
+                        jwt::Algorithm::RS256 => DecodingKey::from_rsa_pem(&pem),
+                        _ => DecodingKey::from_ed_pem(&pem),
+                    }
+                    .with_context(|| format!("failed to parse public key {}", path.display()))?
+                }
+
+                (jwt::Algorithm::HS256, ..) => eyre::bail!("`HS256` keys need a `secret` (and no `public_key`)"),
+                _ => eyre::bail!("`RS256` and `EdDSA` keys need a `public_key` (and no `secret`)"),
+            };
+
+            keys.push(Key {
+                id: key.id.clone(),
+                algorithm: algorithm(key.algorithm),
+                key: decoding,
+            });
+        }
+
+        if let Some(ref path) = config.jwks {
+            let contents = fs::read(path).with_context(|| format!("failed to read JWKS {}", path.display()))?;
+
+            let set: JwkSet = serde_json::from_slice(&contents)
+                .with_context(|| format!("failed to parse JWKS {}", path.display()))?;
+
+            for jwk in &set.keys {
+                let algorithm = match (jwk.common.key_algorithm, &jwk.algorithm) {
+                    (Some(KeyAlgorithm::HS256), _) | (None, AlgorithmParameters::OctetKey(_)) => Algorithm::HS256,
+                    (Some(KeyAlgorithm::RS256), _) | (None, AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+                    (Some(KeyAlgorithm::EdDSA), _) | (None, AlgorithmParameters::OctetKeyPair(_)) => {
+                        Algorithm::EdDSA
+                    }
+                    _ => {
+                        warn!(kid = ?jwk.common.key_id, "skipping JWK with an unsupported algorithm");
+                        continue;
+                    }
+                };
+
+                keys.push(Key {
+                    id: jwk.common.key_id.clone(),
+                    algorithm,
+                    key: DecodingKey::from_jwk(jwk)
+                        .with_context(|| format!("failed to load JWK {:?}", jwk.common.key_id))?,
+                });
+            }
+        }
+
+        if keys.is_empty() {
+            eyre::bail!("`[server.jwt]` is enabled, but no keys were configured");
+        }
+
+        Ok(Verifier(Arc::new(Inner {
+            config: config.clone(),
+            keys,
+        })))
+    }
+
+    /// Checks if requests to `path` need a token.
+    pub fn is_required_for(&self, path: &str) -> bool {
+        self.0.config.is_required_for(path)
+    }
+
+    /// Verifies `token` and checks that it grants access to `path`, returning its
+    /// `sub` claim.
+    pub fn verify(&self, token: &str, path: &str) -> Result<Option<String>, Error> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| Error::Invalid(describe(e.kind())))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.0.config.leeway;
+        validation.validate_nbf = true;
+        match self.0.config.audience.as_slice() {
+            [] => validation.validate_aud = false,
+            audience => validation.set_audience(audience),
+        }
+
+        let candidates = self.0.keys.iter().filter(|key| {
+            key.algorithm == header.alg &&
+                match (&header.kid, &key.id) {
+                    (Some(kid), Some(id)) => kid == id,
+                    _ => true,
+                }
+        });
+
+        let mut claims = None;
+        for key in candidates {
+            match jsonwebtoken::decode::<Map<String, Value>>(token, &key.key, &validation) {
+                Ok(data) => {
+                    claims = Some(data.claims);
+                    break;
+                }
+
+                Err(e) if *e.kind() == ErrorKind::InvalidSignature => continue,
+                Err(e) => return Err(Error::Invalid(describe(e.kind()))),
+            }
+        }
+
+        let claims = claims.ok_or_else(|| Error::Invalid(String::from("no key matches the token's signature")))?;
+        let prefixes = match claims.get(&self.0.config.claim) {
+            Some(Value::String(prefix)) => vec![prefix.as_str()],
+            Some(Value::Array(prefixes)) => prefixes.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+
+        match prefixes.into_iter().any(|prefix| {
+            let prefix = format!("/{}", prefix.trim_start_matches('/'));
+            util::has_path_prefix(path, &prefix)
+        }) {
+            true => Ok(claims.get("sub").and_then(Value::as_str).map(String::from)),
+            false => Err(Error::Forbidden),
+        }
+    }
+}
+
+fn describe(kind: &ErrorKind) -> String {
+    match kind {
+        ErrorKind::ExpiredSignature => String::from("token has expired"),
+        ErrorKind::ImmatureSignature => String::from("token isn't valid yet"),
+        ErrorKind::InvalidAudience => String::from("token wasn't issued for this audience"),
+        ErrorKind::InvalidAlgorithm => String::from("token is signed with an unexpected algorithm"),
+        ErrorKind::MissingRequiredClaim(claim) => format!("token is missing the `{claim}` claim"),
+        ErrorKind::InvalidToken | ErrorKind::Base64(_) | ErrorKind::Json(_) | ErrorKind::Utf8(_) => {
+            String::from("token is malformed")
+        }
+
+        kind => format!("{kind:?}"),
+    }
+}
+
+const fn algorithm(algorithm: jwt::Algorithm) -> Algorithm {
+    match algorithm {
+        jwt::Algorithm::HS256 => Algorithm::HS256,
+        jwt::Algorithm::RS256 => Algorithm::RS256,
+        jwt::Algorithm::EdDSA => Algorithm::EdDSA,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    fn token(kid: Option<&str>, secret: &str, claims: Value) -> String {
+        let header = Header {
+            kid: kid.map(String::from),
+            ..Header::default()
+        };
+
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+    }
+
+    fn verifier() -> Verifier {
+        Verifier::load(&jwt::Config {
+            keys: vec![
+                jwt::Key {
+                    id: Some(String::from("old")),
+                    algorithm: jwt::Algorithm::HS256,
+                    secret: Some(String::from("old-secret")),
+                    public_key: None,
+                },
+                jwt::Key {
+                    id: Some(String::from("new")),
+                    algorithm: jwt::Algorithm::HS256,
+                    secret: Some(String::from("new-secret")),
+                    public_key: None,
+                },
+            ],
+            audience: vec![String::from("hazel")],
+            prefixes: vec![String::from("/private")],
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_verify() {
+        let verifier = verifier();
+        let exp = jsonwebtoken::get_current_timestamp() + 600;
+        let claims = json!({ "exp": exp, "aud": "hazel", "prefixes": ["private/team-a"] });
+
+        assert!(verifier.is_required_for("/private/team-b/a.txt"));
+        assert!(!verifier.is_required_for("/public/a.txt"));
+
+        for (kid, secret) in [(None, "old-secret"), (Some("new"), "new-secret")] {
+            let token = token(kid, secret, claims.clone());
+            assert_eq!(verifier.verify(&token, "/private/team-a/a.txt"), Ok(None));
+            assert_eq!(verifier.verify(&token, "/private/team-ab/a.txt"), Err(Error::Forbidden));
+        }
+
+        assert!(matches!(
+            verifier.verify(
+                &token(Some("old"), "new-secret", claims.clone()),
+                "/private/team-a/a.txt"
+            ),
+            Err(Error::Invalid(_))
+        ));
+
+        let expired = json!({ "exp": exp - 1200, "aud": "hazel", "prefixes": "/private" });
+        assert!(matches!(
+            verifier.verify(&token(None, "new-secret", expired), "/private/a.txt"),
+            Err(Error::Invalid(_))
+        ));
+
+        let audience = json!({ "exp": exp, "aud": "someone-else", "prefixes": "/private" });
+        assert!(matches!(
+            verifier.verify(&token(None, "new-secret", audience), "/private/a.txt"),
+            Err(Error::Invalid(_))
+        ));
+    }
+}
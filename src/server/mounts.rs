@@ -0,0 +1,138 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage backends that are mounted at path prefixes by `[mounts]`.
+
+use crate::{
+    config::{Config, util},
+    storage::{Caches, Storage},
+};
+use eyre::Context;
+use std::sync::Arc;
+
+/// The storage backends of `[mounts]`, ordered from the longest prefix to the shortest
+/// so that the most specific one that a path is in is picked.
+#[derive(Clone, Default)]
+pub struct Mounts(Arc<[Mount]>);
+
+/// A storage backend that is mounted at a path prefix.
+pub struct Mount {
+    /// The prefix, without a trailing slash.
+    prefix: String,
+    strip_prefix: bool,
+
+    /// The storage backend that objects under the prefix are served from.
+    pub storage: Storage,
+
+    /// The configuration with the settings that this mount overrides applied to it.
+    pub config: Config,
+}
+
+impl Mounts {
+    /// Creates the storage backends of the mounts in `config`, which are put behind
+    /// `caches`.
+    pub fn new(config: &Config, caches: &Caches) -> eyre::Result<Mounts> {
+        let mut mounts = Vec::with_capacity(config.mounts.len());
+        for (prefix, mount) in &config.mounts {
+            if !prefix.starts_with('/') {
+                bail!("mount prefix `{prefix}` has to start with a `/`");
+            }
+
+            let mut effective = Config {
+                mounts: Default::default(),
+                hosts: Default::default(),
+                ..config.clone()
+            };
+
+            let storage = caches.attach(
+                Storage::new(mount.storage.clone())
+                    .with_context(|| format!("failed to create the storage of mount `{prefix}`"))?,
+            );
+
+            mount.apply(&mut effective.server);
+            mounts.push(Mount {
+                prefix: prefix.trim_end_matches('/').to_owned(),
+                strip_prefix: mount.strip_prefix,
+                storage,
+                config: effective,
+            });
+        }
+
+        mounts.sort_by(|a, b| b.prefix.len().cmp(&a.prefix.len()));
+        Ok(Mounts(mounts.into()))
+    }
+
+    /// Initializes the storage backends of every mount.
+    pub async fn init(&self) -> eyre::Result<()> {
+        for mount in self.0.iter() {
+            mount
+                .storage
+                .init()
+                .await
+                .with_context(|| format!("failed to initialize the storage of mount `{}/`", mount.prefix))?;
+
+            info!(prefix = %format_args!("{}/", mount.prefix), backend = mount.storage.backend_name(), "mounted storage");
+        }
+
+        Ok(())
+    }
+
+    /// Returns the mount that `path` is in, alongside the path of the object in its
+    /// storage backend (without a leading slash), or `None` if it isn't in any.
+    pub fn resolve<'p>(&self, path: &'p str) -> Option<(&Mount, &'p str)> {
+        let mount = self.0.iter().find(|mount| util::has_path_prefix(path, &mount.prefix))?;
+        let query = match mount.strip_prefix {
+            true => &path[mount.prefix.len()..],
+            false => path,
+        };
+
+        Some((mount, query.trim_start_matches('/')))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mounts;
+    use crate::{config::Config, storage::Caches};
+
+    #[test]
+    fn test_resolve() {
+        let config: Config = toml::from_str(
+            r#"
+            [mounts."/releases"]
+            index_files = ["index.html"]
+            storage.filesystem.directory = "./releases"
+
+            [mounts."/releases/nightly/"]
+            strip_prefix = false
+            storage.filesystem.directory = "./nightly"
+            "#,
+        )
+        .unwrap();
+
+        let mounts = Mounts::new(&config, &Caches::default()).unwrap();
+        let (mount, query) = mounts.resolve("/releases/v1/hazel.tar.gz").unwrap();
+        assert_eq!(query, "v1/hazel.tar.gz");
+        assert_eq!(mount.config.server.index_files, ["index.html"]);
+
+        let (mount, query) = mounts.resolve("/releases/nightly/hazel.tar.gz").unwrap();
+        assert_eq!(query, "releases/nightly/hazel.tar.gz");
+        assert!(mount.config.server.index_files.is_empty());
+
+        assert_eq!(mounts.resolve("/releases").unwrap().1, "");
+        assert!(mounts.resolve("/releasesx/hazel.tar.gz").is_none());
+        assert!(mounts.resolve("/docs/index.html").is_none());
+    }
+}
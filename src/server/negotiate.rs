@@ -0,0 +1,166 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Proactive content negotiation with the `Accept` header (RFC 9110 §12.5.1).
+
+use axum::http::{HeaderMap, header};
+
+/// Picks the media type out of `available` that the client prefers the most from its
+/// `Accept` header, or `None` if none of them are acceptable.
+///
+/// Media types that match a more specific range (i.e, `application/json` over `*/*`)
+/// win when they have the same quality, and any remaining ties go to the one that comes
+/// first in `available`. If no `Accept` header was sent, the first one is picked.
+pub fn preferred<'a>(headers: &HeaderMap, available: &[&'a str]) -> Option<&'a str> {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|value| value.to_str().ok()) else {
+        return available.first().copied();
+    };
+
+    let ranges = parse(accept);
+    let mut best: Option<(&str, f32, u8)> = None;
+
+    for media in available {
+        let Some((quality, specificity)) = score(&ranges, media) else {
+            continue;
+        };
+
+        if quality <= 0.0 {
+            continue;
+        }
+
+        match best {
+            Some((_, q, s)) if (q, s) >= (quality, specificity) => {}
+            _ => best = Some((media, quality, specificity)),
+        }
+    }
+
+    best.map(|(media, ..)| media)
+}
+
+/// Returns the quality that the client gave to the content coding `coding` (like `br`)
+/// in its `Accept-Encoding` header, or `0.0` if it isn't acceptable. `*` matches any
+/// coding that isn't explicitly listed.
+pub fn encoding_quality(headers: &HeaderMap, coding: &str) -> f32 {
+    let Some(accept) = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return 0.0;
+    };
+
+    let codings = parse(accept);
+    codings
+        .iter()
+        .find(|(candidate, _)| candidate == coding)
+        .or_else(|| codings.iter().find(|(candidate, _)| candidate == "*"))
+        .map_or(0.0, |(_, quality)| *quality)
+}
+
+/// Parses the media ranges (or content codings) and their qualities from an `Accept`
+/// (or `Accept-Encoding`) header.
+fn parse(accept: &str) -> Vec<(String, f32)> {
+    accept
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let media = parts.next()?.trim().to_ascii_lowercase();
+            if media.is_empty() {
+                return None;
+            }
+
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            Some((media, quality))
+        })
+        .collect()
+}
+
+/// Returns the quality of the most specific media range that matches `media`, alongside
+/// how specific it was: `2` for an exact match, `1` for `type/*` and `0` for `*/*`.
+fn score(ranges: &[(String, f32)], media: &str) -> Option<(f32, u8)> {
+    let (ty, _) = media.split_once('/')?;
+    ranges
+        .iter()
+        .filter_map(|(range, quality)| {
+            let specificity = match range.split_once('/')? {
+                ("*", "*") => 0,
+                (range_ty, "*") if range_ty == ty => 1,
+                _ if range == media => 2,
+                _ => return None,
+            };
+
+            Some((*quality, specificity))
+        })
+        .max_by_key(|(_, specificity)| *specificity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encoding_quality, preferred};
+    use axum::http::{HeaderMap, HeaderValue, header};
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+
+        headers
+    }
+
+    #[test]
+    fn test_preferred() {
+        let available = ["text/html", "application/json"];
+        assert_eq!(preferred(&HeaderMap::new(), &available), Some("text/html"));
+        assert_eq!(preferred(&accept("*/*"), &available), Some("text/html"));
+        assert_eq!(
+            preferred(&accept("application/json"), &available),
+            Some("application/json")
+        );
+        assert_eq!(
+            preferred(&accept("application/json, */*"), &available),
+            Some("application/json")
+        );
+        assert_eq!(
+            preferred(
+                &accept("text/html,application/xhtml+xml,application/json;q=0.9,*/*;q=0.8"),
+                &available
+            ),
+            Some("text/html")
+        );
+
+        assert_eq!(preferred(&accept("image/png"), &available), None);
+        assert_eq!(
+            preferred(&accept("text/html;q=0, */*"), &available),
+            Some("application/json")
+        );
+    }
+
+    #[test]
+    fn test_encoding_quality() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(encoding_quality(&headers, "br"), 0.0);
+
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("gzip;q=0.5, br"));
+        assert_eq!(encoding_quality(&headers, "br"), 1.0);
+        assert_eq!(encoding_quality(&headers, "gzip"), 0.5);
+        assert_eq!(encoding_quality(&headers, "zstd"), 0.0);
+
+        headers.insert(header::ACCEPT_ENCODING, HeaderValue::from_static("*;q=0.1, gzip;q=0"));
+        assert_eq!(encoding_quality(&headers, "zstd"), 0.1);
+        assert_eq!(encoding_quality(&headers, "gzip"), 0.0);
+    }
+}
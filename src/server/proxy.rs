@@ -0,0 +1,343 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resolves the client's IP address behind trusted reverse proxies, from the
+//! `Forwarded` and `X-Forwarded-For` headers or a PROXY protocol header.
+
+use crate::config::server::access::Cidr;
+use axum::http::{HeaderMap, HeaderName, Request, header::FORWARDED};
+use axum_server::accept::Accept;
+use futures_util::future::BoxFuture;
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    net::TcpStream,
+};
+use tower::Service;
+
+static X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Signature that PROXY protocol v2 headers start with.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Maximum length of a PROXY protocol v1 header, including the trailing `\r\n`.
+const V1_MAX_LENGTH: usize = 107;
+
+/// How long a connection has to send its PROXY protocol header.
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The IP address of the client that made the request, as resolved by the
+/// `client_ip` middleware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// The source address from the PROXY protocol header of the connection, if it came
+/// from a trusted proxy.
+#[derive(Debug, Clone, Copy)]
+pub struct ProxiedPeer(pub SocketAddr);
+
+/// Resolves the IP address of the client behind `peer`. If `peer` is a trusted proxy,
+/// then the hops in the `Forwarded` (or `X-Forwarded-For`) header are walked from the
+/// nearest one, and the first address that isn't a trusted proxy is the client.
+pub fn resolve(peer: IpAddr, headers: &HeaderMap, trusted: &[Cidr]) -> IpAddr {
+    let mut client = peer.to_canonical();
+    if !is_trusted(trusted, client) {
+        return client;
+    }
+
+    for hop in forwarded_hops(headers).into_iter().rev() {
+        // obfuscated or `unknown` identifiers hide the rest of the chain, so the
+        // nearest hop that we know about has to do
+        let Some(hop) = hop else {
+            break;
+        };
+
+        client = hop.to_canonical();
+        if !is_trusted(trusted, client) {
+            break;
+        }
+    }
+
+    client
+}
+
+fn is_trusted(trusted: &[Cidr], ip: IpAddr) -> bool {
+    trusted.iter().any(|cidr| cidr.contains(ip))
+}
+
+/// Returns the hops in the `Forwarded` header (or `X-Forwarded-For` if it isn't
+/// present), from the original client to the nearest proxy.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = headers
+        .get_all(&FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+
+    if headers.contains_key(&FORWARDED) {
+        return values
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .find(|(key, _)| key.eq_ignore_ascii_case("for"))
+                    .and_then(|(_, value)| parse_node(value))
+            })
+            .collect();
+    }
+
+    headers
+        .get_all(&X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(parse_node)
+        .collect()
+}
+
+/// Parses a node like `192.0.2.1`, `"192.0.2.1:8080"` or `"[2001:db8::1]:8080"`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+
+    node.parse()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// An acceptor that reads the PROXY protocol header that every connection has to
+/// start with, and adds its source address to the connection's requests as a
+/// [`ProxiedPeer`] if the connection came from a trusted proxy.
+#[derive(Clone)]
+pub struct ProxyProtocolAcceptor {
+    trusted: Arc<[Cidr]>,
+}
+
+impl ProxyProtocolAcceptor {
+    pub fn new(trusted: &[Cidr]) -> ProxyProtocolAcceptor {
+        ProxyProtocolAcceptor {
+            trusted: Arc::from(trusted),
+        }
+    }
+}
+
+impl<S: Send + 'static> Accept<TcpStream, S> for ProxyProtocolAcceptor {
+    type Stream = TcpStream;
+    type Service = WithProxiedPeer<S>;
+    type Future = BoxFuture<'static, io::Result<(TcpStream, WithProxiedPeer<S>)>>;
+
+    fn accept(&self, mut stream: TcpStream, service: S) -> Self::Future {
+        let trusted = self.trusted.clone();
+        Box::pin(async move {
+            let source = tokio::time::timeout(HEADER_TIMEOUT, read_header(&mut stream))
+                .await
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::TimedOut, "timed out reading PROXY protocol header")
+                })??;
+
+            let peer = stream.peer_addr()?;
+            let peer = source.filter(|_| is_trusted(&trusted, peer.ip().to_canonical()));
+
+            Ok((stream, WithProxiedPeer { inner: service, peer }))
+        })
+    }
+}
+
+/// A service that adds the [`ProxiedPeer`] of its connection to every request.
+#[derive(Clone)]
+pub struct WithProxiedPeer<S> {
+    inner: S,
+    peer: Option<SocketAddr>,
+}
+
+impl<S, B> Service<Request<B>> for WithProxiedPeer<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        if let Some(peer) = self.peer {
+            req.extensions_mut().insert(ProxiedPeer(peer));
+        }
+
+        self.inner.call(req)
+    }
+}
+
+/// Reads a PROXY protocol v1 or v2 header from `stream`, returning its source address
+/// (`None` for `LOCAL` and `UNKNOWN` connections).
+async fn read_header<R: AsyncRead + Unpin>(stream: &mut R) -> io::Result<Option<SocketAddr>> {
+    // both versions' headers are at least 12 bytes long, so this won't read
+    // any of the connection's data
+    let mut prefix = [0; 12];
+    stream.read_exact(&mut prefix).await?;
+
+    if prefix == V2_SIGNATURE {
+        let mut meta = [0; 4];
+        stream.read_exact(&mut meta).await?;
+
+        let mut addresses = vec![0; usize::from(u16::from_be_bytes([meta[2], meta[3]]))];
+        stream.read_exact(&mut addresses).await?;
+
+        return parse_v2(meta[0], meta[1], &addresses)
+            .ok_or_else(|| invalid("malformed PROXY protocol v2 header"));
+    }
+
+    if !prefix.starts_with(b"PROXY ") {
+        return Err(invalid("connection didn't start with a PROXY protocol header"));
+    }
+
+    let mut line = prefix.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(invalid("PROXY protocol v1 header is too long"));
+        }
+
+        line.push(stream.read_u8().await?);
+    }
+
+    std::str::from_utf8(&line)
+        .ok()
+        .and_then(parse_v1)
+        .ok_or_else(|| invalid("malformed PROXY protocol v1 header"))
+}
+
+fn invalid(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Parses a v1 header line like `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n`.
+fn parse_v1(line: &str) -> Option<Option<SocketAddr>> {
+    let mut parts = line.strip_prefix("PROXY ")?.strip_suffix("\r\n")?.split(' ');
+    match parts.next()? {
+        "UNKNOWN" => Some(None),
+        "TCP4" | "TCP6" => {
+            let source: IpAddr = parts.next()?.parse().ok()?;
+            let _destination: IpAddr = parts.next()?.parse().ok()?;
+            let port: u16 = parts.next()?.parse().ok()?;
+            let _destination_port: u16 = parts.next()?.parse().ok()?;
+
+            Some(Some(SocketAddr::new(source, port)))
+        }
+
+        _ => None,
+    }
+}
+
+/// Parses the version/command and family bytes and the addresses of a v2 header.
+fn parse_v2(version_command: u8, family: u8, addresses: &[u8]) -> Option<Option<SocketAddr>> {
+    if version_command >> 4 != 2 {
+        return None;
+    }
+
+    match version_command & 0x0F {
+        // LOCAL: health checks from the proxy itself
+        0x0 => return Some(None),
+        0x1 => {}
+        _ => return None,
+    }
+
+    match family >> 4 {
+        0x1 if addresses.len() >= 12 => {
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&addresses[..4]).ok()?);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+
+            Some(Some(SocketAddr::new(ip.into(), port)))
+        }
+
+        0x2 if addresses.len() >= 36 => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&addresses[..16]).ok()?);
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+
+            Some(Some(SocketAddr::new(ip.into(), port)))
+        }
+
+        // AF_UNSPEC and AF_UNIX don't have an IP address
+        0x0 | 0x3 => Some(None),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let trusted: Vec<Cidr> = vec!["10.0.0.0/8".parse().unwrap(), "2001:db8::/32".parse().unwrap()];
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let headers = |name: &'static str, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, value.parse().unwrap());
+            headers
+        };
+
+        let xff = headers("x-forwarded-for", "198.51.100.7, 203.0.113.5, 10.0.0.2");
+        assert_eq!(resolve(ip("10.0.0.1"), &xff, &trusted), ip("203.0.113.5"));
+        assert_eq!(resolve(ip("::ffff:10.0.0.1"), &xff, &trusted), ip("203.0.113.5"));
+        assert_eq!(resolve(ip("192.0.2.1"), &xff, &trusted), ip("192.0.2.1"));
+
+        let forwarded = headers(
+            "forwarded",
+            r#"for=198.51.100.7, for="[2001:db8::1]:4711";proto=https, For="10.0.0.3:80""#,
+        );
+
+        assert_eq!(resolve(ip("10.0.0.1"), &forwarded, &trusted), ip("198.51.100.7"));
+
+        let unknown = headers("forwarded", "for=198.51.100.7, for=unknown, for=10.0.0.3");
+        assert_eq!(resolve(ip("10.0.0.1"), &unknown, &trusted), ip("10.0.0.3"));
+        assert_eq!(resolve(ip("10.0.0.1"), &HeaderMap::new(), &trusted), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_read_header() {
+        let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let read = |bytes: &[u8]| {
+            let mut stream = bytes;
+            let result = rt.block_on(read_header(&mut stream));
+            (result.ok(), stream.to_vec())
+        };
+
+        let (source, rest) = read(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n");
+        assert_eq!(source, Some(Some("192.0.2.1:56324".parse().unwrap())));
+        assert_eq!(rest, b"GET / HTTP/1.1\r\n");
+
+        assert_eq!(read(b"PROXY UNKNOWN\r\n").0, Some(None));
+        assert_eq!(read(b"GET / HTTP/1.1\r\n").0, None);
+
+        let mut v2 = V2_SIGNATURE.to_vec();
+        v2.extend([0x21, 0x11, 0, 12, 192, 0, 2, 1, 198, 51, 100, 1, 0xDC, 0x04, 0x01, 0xBB]);
+        v2.extend(b"GET");
+
+        let (source, rest) = read(&v2);
+        assert_eq!(source, Some(Some("192.0.2.1:56324".parse().unwrap())));
+        assert_eq!(rest, b"GET");
+    }
+}
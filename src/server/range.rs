@@ -0,0 +1,258 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for [HTTP Range Requests] (RFC 9110 §14).
+//!
+//! [HTTP Range Requests]: https://www.rfc-editor.org/rfc/rfc9110#section-14
+
+use super::conditional;
+use crate::storage::{ByteStream, Metadata};
+use axum::body::Bytes;
+use futures_util::{StreamExt, TryStreamExt, future, stream};
+use rand::distr::{Alphanumeric, SampleString};
+use std::{io, ops::Range};
+
+/// Maximum amount of ranges that we are willing to serve in a single `multipart/byteranges`
+/// response. Requests that ask for more (after overlapping ranges are coalesced) are served
+/// the full representation instead.
+const MAX_RANGES: usize = 16;
+
+/// The outcome of evaluating a `Range` header against a representation.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Ranges {
+    /// The header should be ignored and the full representation should be served.
+    Full,
+
+    /// One or more satisfiable ranges, sorted and with no overlaps.
+    Partial(Vec<Range<u64>>),
+
+    /// None of the requested ranges were satisfiable.
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header value and resolves it against a representation that is
+/// `size` bytes long.
+///
+/// As allowed by RFC 9110, a header that is syntactically invalid or that uses a range
+/// unit other than `bytes` is ignored.
+pub fn parse(header: &str, size: u64) -> Ranges {
+    let Some((unit, specs)) = header.trim().split_once('=') else {
+        return Ranges::Full;
+    };
+
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return Ranges::Full;
+    }
+
+    let mut ranges = Vec::new();
+    let mut any = false;
+    for spec in specs.split(',').map(str::trim).filter(|spec| !spec.is_empty()) {
+        let Some((first, last)) = spec.split_once('-') else {
+            return Ranges::Full;
+        };
+
+        any = true;
+        let range = match (first.trim(), last.trim()) {
+            // `-{suffix}`: the last `suffix` bytes
+            ("", suffix) => {
+                let Ok(suffix) = suffix.parse::<u64>() else {
+                    return Ranges::Full;
+                };
+
+                if suffix == 0 || size == 0 {
+                    continue;
+                }
+
+                size.saturating_sub(suffix)..size
+            }
+
+            // `{first}-`: everything from `first` until the end
+            (first, "") => {
+                let Ok(first) = first.parse::<u64>() else {
+                    return Ranges::Full;
+                };
+
+                if first >= size {
+                    continue;
+                }
+
+                first..size
+            }
+
+            // `{first}-{last}`: inclusive range
+            (first, last) => {
+                let (Ok(first), Ok(last)) = (first.parse::<u64>(), last.parse::<u64>()) else {
+                    return Ranges::Full;
+                };
+
+                if last < first {
+                    return Ranges::Full;
+                }
+
+                if first >= size {
+                    continue;
+                }
+
+                first..last.min(size - 1) + 1
+            }
+        };
+
+        ranges.push(range);
+    }
+
+    if !any {
+        return Ranges::Full;
+    }
+
+    if ranges.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+
+    let ranges = coalesce(ranges);
+    if ranges.len() > MAX_RANGES {
+        return Ranges::Full;
+    }
+
+    Ranges::Partial(ranges)
+}
+
+/// Sorts the given ranges and merges the ones that overlap or are adjacent.
+fn coalesce(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => {
+                last.end = last.end.max(range.end);
+            }
+
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// Evaluates an `If-Range` header: returns `true` if the `Range` header should be
+/// honoured, or `false` if the representation has changed and it should be served in
+/// full instead.
+pub fn if_range(header: &str, metadata: &Metadata) -> bool {
+    let header = header.trim();
+
+    // entity tags always start with a quote (or `W/` for a weak tag, which can never
+    // match in `If-Range`), everything else is treated as an HTTP date.
+    if header.starts_with('"') || header.starts_with("W/") {
+        return conditional::matches(header, metadata.etag.as_deref(), false);
+    }
+
+    match (httpdate::parse_http_date(header), metadata.last_modified) {
+        (Ok(date), Some(last_modified)) => httpdate::fmt_http_date(date) == httpdate::fmt_http_date(last_modified),
+        _ => false,
+    }
+}
+
+/// Formats the value of a `Content-Range` header for a satisfiable range.
+pub fn content_range(range: &Range<u64>, size: u64) -> String {
+    format!("bytes {}-{}/{size}", range.start, range.end - 1)
+}
+
+/// Formats the value of a `Content-Range` header for a `416 Range Not Satisfiable`
+/// response.
+pub fn unsatisfied_content_range(size: u64) -> String {
+    format!("bytes */{size}")
+}
+
+/// Generates a boundary to separate each part of a `multipart/byteranges` body.
+pub fn boundary() -> String {
+    Alphanumeric.sample_string(&mut rand::rng(), 24)
+}
+
+/// Builds a `multipart/byteranges` body from each range. Each range is only opened with
+/// `open` once the previous part was fully sent, so only one range is being read from the
+/// storage backend at a time.
+///
+/// This returns the length of the body alongside it, so it can be sent in the
+/// `Content-Length` header.
+pub fn multipart<F, Fut>(
+    boundary: &str,
+    content_type: &str,
+    size: u64,
+    ranges: Vec<Range<u64>>,
+    mut open: F,
+) -> (u64, ByteStream)
+where
+    F: FnMut(Range<u64>) -> Fut + Send + 'static,
+    Fut: Future<Output = io::Result<ByteStream>> + Send + 'static,
+{
+    let mut length = 0;
+    let mut parts = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        let head = Bytes::from(format!(
+            "\r\n--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {}\r\n\r\n",
+            content_range(&range, size)
+        ));
+
+        length += head.len() as u64 + (range.end - range.start);
+        parts.push((head, range));
+    }
+
+    let tail = Bytes::from(format!("\r\n--{boundary}--\r\n"));
+    length += tail.len() as u64;
+
+    let body = stream::iter(parts)
+        .flat_map(move |(head, range)| {
+            stream::once(future::ready(Ok(head))).chain(stream::once(open(range)).try_flatten())
+        })
+        .chain(stream::once(future::ready(Ok(tail))));
+
+    (length, Box::pin(body))
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::{Ranges, parse};
+
+    #[test]
+    fn test_parse_single_ranges() {
+        assert_eq!(parse("bytes=0-99", 1000), Ranges::Partial(vec![0..100]));
+        assert_eq!(parse("bytes=500-", 1000), Ranges::Partial(vec![500..1000]));
+        assert_eq!(parse("bytes=-200", 1000), Ranges::Partial(vec![800..1000]));
+        assert_eq!(parse("bytes=-2000", 1000), Ranges::Partial(vec![0..1000]));
+        assert_eq!(parse("bytes=900-5000", 1000), Ranges::Partial(vec![900..1000]));
+    }
+
+    #[test]
+    fn test_parse_multiple_ranges() {
+        assert_eq!(parse("bytes=0-9, 20-29", 100), Ranges::Partial(vec![0..10, 20..30]));
+
+        assert_eq!(parse("bytes=20-29,0-25", 100), Ranges::Partial(vec![0..30]));
+        assert_eq!(parse("bytes=0-9,10-19", 100), Ranges::Partial(vec![0..20]));
+        assert_eq!(parse("bytes=0-9,200-300", 100), Ranges::Partial(vec![0..10]));
+    }
+
+    #[test]
+    fn test_parse_unsatisfiable_or_ignored() {
+        assert_eq!(parse("bytes=1000-", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=-0", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=0-", 0), Ranges::Unsatisfiable);
+
+        assert_eq!(parse("bytes=10-5", 1000), Ranges::Full);
+        assert_eq!(parse("bytes=abc", 1000), Ranges::Full);
+        assert_eq!(parse("items=0-5", 1000), Ranges::Full);
+        assert_eq!(parse("bytes=", 1000), Ranges::Full);
+    }
+}
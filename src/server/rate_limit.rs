@@ -0,0 +1,388 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Token-bucket rate limits and concurrency limits from `[[server.rate_limits]]`.
+
+use crate::config::server::rate_limit::{Key, Rule};
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+static RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+static RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+static RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Amount of buckets that a rule keeps before buckets that are full again (and so
+/// are the same as new ones) are pruned.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Enforces the `[[server.rate_limits]]` rules.
+#[derive(Clone)]
+pub struct Limiter(Arc<[State]>);
+
+struct State {
+    rule: Rule,
+    buckets: Mutex<Buckets>,
+}
+
+struct Buckets {
+    map: HashMap<String, Bucket>,
+    prune_at: usize,
+}
+
+struct Bucket {
+    requests: f64,
+    bytes: f64,
+    in_flight: u32,
+    updated: Instant,
+}
+
+/// The budget of requests that a client has left, as sent in the `RateLimit-*` headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset: u64,
+}
+
+impl Status {
+    /// Adds the `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers,
+    /// unless they already describe a budget with less remaining.
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        if let Some(remaining) = headers
+            .get(&RATELIMIT_REMAINING)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u32>().ok()) &&
+            remaining <= self.remaining
+        {
+            return;
+        }
+
+        headers.insert(&RATELIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(&RATELIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(&RATELIMIT_RESET, HeaderValue::from(self.reset));
+    }
+}
+
+/// A request that was rejected by a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limited {
+    /// Amount of seconds until the request would be allowed.
+    pub retry_after: u64,
+
+    /// The budget of the rule that rejected the request, if it limits requests.
+    pub status: Option<Status>,
+}
+
+/// A request that was allowed. It charges the bytes of the response to the budgets
+/// of the rules that applied, and counts as in flight until it's dropped.
+pub struct Permit {
+    limiter: Limiter,
+    held: Vec<(usize, String)>,
+    status: Option<Status>,
+}
+
+impl Limiter {
+    pub fn new(rules: &[Rule]) -> Limiter {
+        Limiter(
+            rules
+                .iter()
+                .map(|rule| State {
+                    rule: rule.clone(),
+                    buckets: Mutex::new(Buckets {
+                        map: HashMap::new(),
+                        prune_at: PRUNE_THRESHOLD,
+                    }),
+                })
+                .collect(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Takes a request from the budget of every rule that applies to a request to
+    /// `path` and isn't keyed by identity, or rejects it if any of them is used up.
+    pub fn acquire(&self, path: &str, ip: Option<IpAddr>) -> Result<Permit, Limited> {
+        self.acquire_at(Instant::now(), path, ip, None, false)
+    }
+
+    /// Like [`Limiter::acquire`], but for the rules that are keyed by identity, which
+    /// are only known once the request has been authenticated.
+    pub fn acquire_identity(
+        &self,
+        path: &str,
+        ip: Option<IpAddr>,
+        identity: Option<&str>,
+    ) -> Result<Permit, Limited> {
+        self.acquire_at(Instant::now(), path, ip, identity, true)
+    }
+
+    fn acquire_at(
+        &self,
+        now: Instant,
+        path: &str,
+        ip: Option<IpAddr>,
+        identity: Option<&str>,
+        authenticated: bool,
+    ) -> Result<Permit, Limited> {
+        let mut permit = Permit {
+            limiter: self.clone(),
+            held: Vec::new(),
+            status: None,
+        };
+
+        for (index, state) in self.0.iter().enumerate() {
+            if (state.rule.key == Key::Identity) != authenticated {
+                continue;
+            }
+
+            let Some(prefix) = state.rule.prefix_for(path) else {
+                continue;
+            };
+
+            let key = match state.rule.key {
+                Key::Ip => ip.map(|ip| ip.to_string()),
+                Key::Identity => identity
+                    .map(|identity| format!("identity:{identity}"))
+                    .or_else(|| ip.map(|ip| format!("ip:{ip}"))),
+
+                Key::Prefix => Some(prefix.to_owned()),
+            };
+
+            let Some(key) = key else {
+                continue;
+            };
+
+            let status = match state.acquire(now, &key) {
+                Ok(status) => status,
+                Err(limited) => {
+                    permit.refund();
+                    return Err(limited);
+                }
+            };
+
+            permit.held.push((index, key));
+
+            if let Some(status) = status &&
+                permit.status.is_none_or(|current| status.remaining < current.remaining)
+            {
+                permit.status = Some(status);
+            }
+        }
+
+        Ok(permit)
+    }
+}
+
+impl State {
+    fn acquire(&self, now: Instant, key: &str) -> Result<Option<Status>, Limited> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.map.len() >= buckets.prune_at {
+            buckets.map.retain(|_, bucket| {
+                bucket.refill(&self.rule, now);
+                bucket.in_flight > 0 || !bucket.is_full(&self.rule)
+            });
+
+            buckets.prune_at = PRUNE_THRESHOLD.max(buckets.map.len() * 2);
+        }
+
+        let bucket = buckets.map.entry(key.to_owned()).or_insert_with(|| Bucket {
+            requests: self.rule.request_burst().unwrap_or_default(),
+            bytes: self.rule.byte_burst().unwrap_or_default(),
+            in_flight: 0,
+            updated: now,
+        });
+
+        bucket.refill(&self.rule, now);
+
+        let limited = |retry_after: f64, bucket: &Bucket| Limited {
+            retry_after: (retry_after.ceil() as u64).max(1),
+            status: bucket.status(&self.rule),
+        };
+
+        if let Some(concurrency) = self.rule.concurrency &&
+            bucket.in_flight >= concurrency
+        {
+            return Err(limited(1.0, bucket));
+        }
+
+        if let Some(rate) = self.rule.requests_per_second &&
+            bucket.requests < 1.0
+        {
+            return Err(limited((1.0 - bucket.requests) / rate, bucket));
+        }
+
+        if let Some(rate) = self.rule.bytes_per_second &&
+            bucket.bytes <= 0.0
+        {
+            return Err(limited((1.0 - bucket.bytes) / rate as f64, bucket));
+        }
+
+        if self.rule.requests_per_second.is_some() {
+            bucket.requests -= 1.0;
+        }
+
+        bucket.in_flight += 1;
+        Ok(bucket.status(&self.rule))
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, rule: &Rule, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.updated = now;
+
+        if let (Some(rate), Some(burst)) = (rule.requests_per_second, rule.request_burst()) {
+            self.requests = (self.requests + elapsed * rate).min(burst);
+        }
+
+        if let (Some(rate), Some(burst)) = (rule.bytes_per_second, rule.byte_burst()) {
+            self.bytes = (self.bytes + elapsed * rate as f64).min(burst);
+        }
+    }
+
+    fn is_full(&self, rule: &Rule) -> bool {
+        rule.request_burst().is_none_or(|burst| self.requests >= burst) &&
+            rule.byte_burst().is_none_or(|burst| self.bytes >= burst)
+    }
+
+    fn status(&self, rule: &Rule) -> Option<Status> {
+        let rate = rule.requests_per_second?;
+        let burst = rule.request_burst()?;
+
+        Some(Status {
+            limit: burst as u32,
+            remaining: self.requests.max(0.0).floor() as u32,
+            reset: ((burst - self.requests) / rate).max(0.0).ceil() as u64,
+        })
+    }
+}
+
+impl Permit {
+    /// The budget of requests that the client has left, from the rule with the least.
+    pub fn status(&self) -> Option<Status> {
+        self.status
+    }
+
+    /// Charges `bytes` of the response to the budgets of the rules that limit bytes.
+    pub fn charge(&self, bytes: usize) {
+        for (index, key) in &self.held {
+            let state = &self.limiter.0[*index];
+            if state.rule.bytes_per_second.is_none() {
+                continue;
+            }
+
+            if let Some(bucket) = state.buckets.lock().unwrap().map.get_mut(key) {
+                bucket.bytes -= bytes as f64;
+            }
+        }
+    }
+}
+
+impl Permit {
+    /// Gives back the requests that were taken from the previous rules' budgets when
+    /// a later rule rejects the request.
+    fn refund(&self) {
+        for (index, key) in &self.held {
+            let state = &self.limiter.0[*index];
+            if let (Some(burst), Some(bucket)) = (
+                state.rule.request_burst(),
+                state.buckets.lock().unwrap().map.get_mut(key),
+            ) {
+                bucket.requests = (bucket.requests + 1.0).min(burst);
+            }
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        for (index, key) in &self.held {
+            if let Some(bucket) = self.limiter.0[*index].buckets.lock().unwrap().map.get_mut(key) {
+                bucket.in_flight = bucket.in_flight.saturating_sub(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn rule(toml: &str) -> Rule {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_requests() {
+        let limiter = Limiter::new(&[rule("requests_per_second = 2\nburst = 3")]);
+        let start = Instant::now();
+        let ip = Some("192.0.2.1".parse().unwrap());
+        let other = Some("192.0.2.2".parse().unwrap());
+
+        for remaining in [2, 1, 0] {
+            let permit = limiter.acquire_at(start, "/a.txt", ip, None, false).unwrap();
+            assert_eq!(permit.status().map(|status| status.remaining), Some(remaining));
+        }
+
+        let limited = limiter.acquire_at(start, "/a.txt", ip, None, false).err().unwrap();
+        assert_eq!(limited.retry_after, 1);
+        assert!(limiter.acquire_at(start, "/a.txt", other, None, false).is_ok());
+
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.acquire_at(later, "/a.txt", ip, None, false).is_ok());
+        assert!(limiter.acquire_at(later, "/a.txt", ip, None, false).is_err());
+    }
+
+    #[test]
+    fn test_bytes_and_concurrency() {
+        let limiter = Limiter::new(&[
+            rule("key = \"prefix\"\nprefixes = [\"/big\"]\nbytes_per_second = 100"),
+            rule("key = \"identity\"\nconcurrency = 1"),
+        ]);
+
+        let start = Instant::now();
+        let ip = Some("192.0.2.1".parse().unwrap());
+
+        let permit = limiter.acquire_at(start, "/big/a.bin", ip, None, false).unwrap();
+        let held = limiter.acquire_at(start, "/big/a.bin", ip, Some("noel"), true).unwrap();
+        assert!(limiter.acquire_at(start, "/a.txt", ip, Some("noel"), true).is_err());
+        assert!(limiter.acquire_at(start, "/a.txt", ip, Some("someone"), true).is_ok());
+
+        permit.charge(250);
+        drop((permit, held));
+
+        // the prefix's budget is shared by everyone and is in debt for 1.5 seconds
+        let limited = limiter.acquire_at(start, "/big/b.bin", ip, None, false).err().unwrap();
+        assert_eq!(limited.retry_after, 2);
+        assert!(limiter.acquire_at(start, "/a.txt", ip, Some("noel"), true).is_ok());
+        assert!(
+            limiter
+                .acquire_at(start + Duration::from_secs(2), "/big/b.bin", ip, None, false)
+                .is_ok()
+        );
+
+        // rules keyed by identity are only applied once the request is authenticated,
+        // and the other rules only before that
+        assert!(limiter.acquire_at(start, "/big/b.bin", ip, Some("noel"), true).is_ok());
+    }
+}
@@ -13,33 +13,92 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::middlewares;
-use crate::config::Config;
+use super::{
+    access_log, autoindex, basic_auth,
+    canonical::CanonicalPath,
+    compression::{self, Precompressed},
+    conditional::{self, Precondition},
+    hosts::Hosts,
+    jwt,
+    middlewares::{self, XRequestId},
+    mounts::Mounts,
+    negotiate,
+    range::{self, Ranges},
+    rate_limit,
+};
+use crate::{
+    config::Config,
+    metrics,
+    storage::{ByteStream, Entry, Metadata, Storage},
+};
 use axum::{
     Extension, Json, Router,
     body::Body,
-    extract::Path,
-    http::{StatusCode, header},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri, header},
     response::{IntoResponse, Response},
     routing,
 };
-use azalia::remi::{
-    StorageService,
-    core::{Blob, StorageService as _},
-};
+use futures_util::StreamExt;
 use serde_json::json;
-use std::any::Any;
+use std::{any::Any, io};
 
-pub fn create_router(storage: StorageService, config: Config) -> Router {
-    Router::new()
-        .route("/healthz", routing::get(healthz))
-        .route("/{*file}", routing::get(query))
+/// Header that says whether if an object was served from `[server.cache]`.
+static X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_router(
+    storage: Storage,
+    config: Config,
+    jwt: Option<jwt::Verifier>,
+    realms: basic_auth::Realms,
+    limiter: rate_limit::Limiter,
+    access_log: Option<access_log::AccessLog>,
+    mounts: Mounts,
+    hosts: Hosts,
+) -> Router {
+    let mut router = Router::new()
+        .route("/{*file}", routing::get(query).head(head))
         .route("/", routing::get(main))
+        .route_layer(axum::middleware::from_fn(middlewares::identity_rate_limit))
+        .route_layer(axum::middleware::from_fn(middlewares::signature))
+        .route_layer(axum::middleware::from_fn(middlewares::jwt))
+        .route_layer(axum::middleware::from_fn(middlewares::basic_auth))
+        .route_layer(axum::middleware::from_fn(middlewares::access))
+        .route_layer(axum::middleware::from_fn(middlewares::rate_limit))
+        .route_layer(axum::middleware::from_fn(middlewares::canonical_path))
+        .route("/healthz", routing::get(healthz));
+
+    // metrics that are served by the main server are only behind the access rules: they
+    // aren't objects, so the rate limits and authentication don't apply to them
+    if let Some(ref metrics) = config.server.metrics &&
+        metrics.listen.is_none()
+    {
+        router = router.merge(
+            Router::new()
+                .route(&metrics.path, routing::get(scrape))
+                .route_layer(axum::middleware::from_fn(middlewares::access))
+                .route_layer(axum::middleware::from_fn(middlewares::canonical_path)),
+        );
+    }
+
+    router
         .layer(sentry_tower::NewSentryLayer::new_from_top())
         .layer(sentry_tower::SentryHttpLayer::new().enable_transaction())
         .layer(tower_http::catch_panic::CatchPanicLayer::custom(panic_handler))
+        .layer(axum::middleware::from_fn(middlewares::errors))
+        .layer(compression::layer(&config))
+        .layer(axum::middleware::from_fn(middlewares::weaken_etag))
         .layer(axum::middleware::from_fn(middlewares::log))
         .layer(axum::middleware::from_fn(middlewares::request_id))
+        .layer(axum::middleware::from_fn(middlewares::client_ip))
+        .layer(axum::middleware::from_fn(middlewares::headers))
+        .layer(axum::middleware::from_fn(middlewares::host))
+        .layer(Extension(jwt))
+        .layer(Extension(access_log))
+        .layer(Extension(realms))
+        .layer(Extension(limiter))
+        .layer(Extension(mounts))
+        .layer(Extension(hosts))
         .layer(Extension(storage))
         .layer(Extension(config))
 }
@@ -64,8 +123,33 @@ fn panic_handler(message: Box<dyn Any + Send + 'static>) -> Response<Body> {
         .unwrap()
 }
 
+#[allow(clippy::too_many_arguments)]
 #[cfg_attr(debug_assertions, axum::debug_handler)]
-async fn main(Extension(config): Extension<Config>) -> impl IntoResponse {
+async fn main(
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    Extension(storage): Extension<Storage>,
+    Extension(config): Extension<Config>,
+    Extension(mounts): Extension<Mounts>,
+    Extension(id): Extension<XRequestId>,
+    Extension(path): Extension<CanonicalPath>,
+) -> Response<Body> {
+    let (storage, mounted, query) = mount(&mounts, storage, &config, "/", &id);
+
+    // the root only serves from the storage backend if something was configured to be
+    // served from it, so that we don't do a pointless lookup on every request
+    if !mounted.server.index_files.is_empty() ||
+        mounted.server.fallback.is_some() ||
+        mounted.server.autoindex.is_some()
+    {
+        match serve(&storage, mounted, query, &method, &path, &uri, &headers).await {
+            Ok(response) if response.status() != StatusCode::NOT_FOUND => return response,
+            Err(response) if response.0 != StatusCode::NOT_FOUND => return response.into_response(),
+            _ => {}
+        }
+    }
+
     Json(json!({
         "hello": "world",
         "server": config.server_name.unwrap_or("unknown".into()),
@@ -74,6 +158,7 @@ async fn main(Extension(config): Extension<Config>) -> impl IntoResponse {
             "timestamp": crate::BUILD_TIMESTAMP,
         }
     }))
+    .into_response()
 }
 
 #[cfg_attr(debug_assertions, axum::debug_handler)]
@@ -81,21 +166,362 @@ async fn healthz() -> &'static str {
     "Ok."
 }
 
-#[instrument(name = "hazel.http.proxy", skip(storage))]
+/// Creates the router of the separate listener that serves metrics from `path`, if
+/// `server.metrics.listen` is set.
+pub fn create_metrics_router(path: &str) -> Router {
+    Router::new().route(path, routing::get(scrape))
+}
+
+#[cfg_attr(debug_assertions, axum::debug_handler)]
+async fn scrape() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, metrics::CONTENT_TYPE)], metrics::encode())
+}
+
+#[instrument(name = "hazel.http.proxy", skip(path, uri, headers, storage, config, mounts, id), fields(path = %path))]
+#[allow(clippy::too_many_arguments)]
 #[cfg_attr(debug_assertions, axum::debug_handler)]
 async fn query(
-    Path(path): Path<String>,
-    Extension(storage): Extension<StorageService>,
+    Extension(path): Extension<CanonicalPath>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    Extension(storage): Extension<Storage>,
+    Extension(config): Extension<Config>,
+    Extension(mounts): Extension<Mounts>,
+    Extension(id): Extension<XRequestId>,
 ) -> Result<Response<Body>, (StatusCode, Json<serde_json::Value>)> {
-    let query = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
-    let query = match storage {
-        StorageService::Filesystem(_) => format!("./{}", query.join("/")),
-        _ => format!("/{}", query.join("/")),
-    };
+    let (storage, config, query) = mount(&mounts, storage, &config, &path, &id);
 
     info!(%query, "performing query");
-    let Some(blob) = storage
-        .blob(&query)
+    serve(&storage, config, query, &method, &path, &uri, &headers).await
+}
+
+/// Picks where the object at `path` is served from: the mount that it is in, or the
+/// `[storage]` table if it isn't in any. Returns the storage backend, the configuration
+/// with the mount's settings applied, and the path of the object in that backend.
+fn mount<'a>(
+    mounts: &'a Mounts,
+    storage: Storage,
+    config: &'a Config,
+    path: &'a str,
+    id: &XRequestId,
+) -> (Storage, &'a Config, &'a str) {
+    match mounts.resolve(path) {
+        Some((mount, query)) => (mount.storage.with_request_id(id), &mount.config, query),
+        None => (storage, config, path.trim_start_matches('/')),
+    }
+}
+
+/// Serves the object that `query` resolves to with [`resolve`].
+async fn serve(
+    storage: &Storage,
+    config: &Config,
+    query: &str,
+    method: &Method,
+    path: &CanonicalPath,
+    uri: &Uri,
+    headers: &HeaderMap,
+) -> Result<Response<Body>, (StatusCode, Json<serde_json::Value>)> {
+    let (query, metadata, validators) = match resolve(storage, config, query, method, path, uri, headers).await {
+        Ok(found) => found,
+        Err(response) => return Ok(response),
+    };
+
+    let query = query.as_str();
+    let precompressed = validators.contains_key(header::CONTENT_ENCODING);
+
+    // Range requests are only defined for GET, so the `Range` header is ignored for
+    // everything else.
+    let ranges = match headers.get(header::RANGE).and_then(|value| value.to_str().ok()) {
+        Some(value) if *method == Method::GET => {
+            let honoured = headers
+                .get(header::IF_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .is_none_or(|value| range::if_range(value, &metadata));
+
+            match honoured {
+                true => range::parse(value, metadata.size),
+                false => Ranges::Full,
+            }
+        }
+
+        _ => Ranges::Full,
+    };
+
+    let mut response = Response::builder().header(header::ACCEPT_RANGES, "bytes");
+    response.headers_mut().unwrap().extend(validators);
+    if precompressed {
+        response = response.extension(Precompressed);
+    }
+
+    if let Some(status) = cache_status(storage, query) {
+        response = response.header(&X_CACHE, status);
+    }
+
+    match ranges {
+        Ranges::Full => {
+            let stream = read(storage, query, None).await?;
+            Ok(response
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, &metadata.content_type)
+                .header(header::CONTENT_LENGTH, metadata.size)
+                .body(body(stream))
+                .unwrap())
+        }
+
+        Ranges::Partial(ranges) if ranges.len() == 1 => {
+            let range = ranges.into_iter().next().unwrap();
+            let stream = read(storage, query, Some(range.clone())).await?;
+
+            Ok(response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_TYPE, &metadata.content_type)
+                .header(header::CONTENT_RANGE, range::content_range(&range, metadata.size))
+                .header(header::CONTENT_LENGTH, range.end - range.start)
+                .body(body(stream))
+                .unwrap())
+        }
+
+        Ranges::Partial(ranges) => {
+            let boundary = range::boundary();
+            let (length, stream) = range::multipart(&boundary, &metadata.content_type, metadata.size, ranges, {
+                let storage = storage.clone();
+                let query = query.to_owned();
+
+                move |range| {
+                    let storage = storage.clone();
+                    let query = query.clone();
+
+                    async move {
+                        match storage.read(&query, Some(range)).await {
+                            Ok(Some(stream)) => Ok(stream),
+                            Ok(None) => Err(io::Error::new(io::ErrorKind::NotFound, "object was deleted")),
+                            Err(e) => {
+                                error!(error = %e, query, "unable to read range of object");
+                                Err(io::Error::other(e))
+                            }
+                        }
+                    }
+                }
+            });
+
+            Ok(response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    header::CONTENT_TYPE,
+                    format!("multipart/byteranges; boundary={boundary}"),
+                )
+                .header(header::CONTENT_LENGTH, length)
+                .body(body(stream))
+                .unwrap())
+        }
+
+        Ranges::Unsatisfiable => Ok((
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [
+                (header::ACCEPT_RANGES, String::from("bytes")),
+                (header::CONTENT_RANGE, range::unsatisfied_content_range(metadata.size)),
+            ],
+            Json(json!({
+                "status": "range_not_satisfiable",
+                "message": "none of the requested ranges can be satisfied",
+                "context": {
+                    "query": query
+                }
+            })),
+        )
+            .into_response()),
+    }
+}
+
+/// Handles `HEAD` requests for objects. Unlike [`query`], this only looks up the metadata
+/// of the object from the storage backend and never reads its contents.
+#[instrument(name = "hazel.http.proxy.head", skip(path, uri, headers, storage, config, mounts, id), fields(path = %path))]
+#[cfg_attr(debug_assertions, axum::debug_handler)]
+async fn head(
+    Extension(path): Extension<CanonicalPath>,
+    uri: Uri,
+    headers: HeaderMap,
+    Extension(storage): Extension<Storage>,
+    Extension(config): Extension<Config>,
+    Extension(mounts): Extension<Mounts>,
+    Extension(id): Extension<XRequestId>,
+) -> Response<Body> {
+    let (storage, config, query) = mount(&mounts, storage, &config, &path, &id);
+
+    info!(%query, "performing metadata-only query");
+    let (path, metadata, validators) =
+        match resolve(&storage, config, query, &Method::HEAD, &path, &uri, &headers).await {
+            Ok(found) => found,
+
+            // bodies (like a directory listing's) are discarded by the server for `HEAD` requests
+            Err(response) => return response,
+        };
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_TYPE, &metadata.content_type)
+        .header(header::CONTENT_LENGTH, metadata.size);
+
+    if validators.contains_key(header::CONTENT_ENCODING) {
+        response = response.extension(Precompressed);
+    }
+
+    if let Some(status) = cache_status(&storage, &path) {
+        response = response.header(&X_CACHE, status);
+    }
+
+    response.headers_mut().unwrap().extend(validators);
+    response.body(Body::empty()).unwrap()
+}
+
+/// Returns the value of the `X-Cache` header for the object at `query`: `HIT` if its
+/// contents are cached, or `MISS` if they have to be read from the storage backend.
+/// `None` is returned if the cache is disabled.
+fn cache_status(storage: &Storage, query: &str) -> Option<&'static str> {
+    storage.is_cached(query).map(|cached| match cached {
+        true => "HIT",
+        false => "MISS",
+    })
+}
+
+/// Resolves the object that should be served for `query` with [`locate`], then picks
+/// the precompressed variant of it that the client prefers, if any.
+///
+/// When a variant is picked, its path and metadata are returned instead (but with the
+/// original's content type), alongside a `Content-Encoding` header.
+async fn resolve(
+    storage: &Storage,
+    config: &Config,
+    query: &str,
+    method: &Method,
+    path: &CanonicalPath,
+    uri: &Uri,
+    headers: &HeaderMap,
+) -> Result<(String, Metadata, HeaderMap), Response<Body>> {
+    let (path, metadata, mut validators) = locate(storage, config, query, method, path, uri, headers).await?;
+    if config.server.precompressed.is_empty() {
+        return Ok((path, metadata, validators));
+    }
+
+    validators.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+
+    let mut candidates = config
+        .server
+        .precompressed
+        .iter()
+        .map(|algorithm| (algorithm, negotiate::encoding_quality(headers, algorithm.coding())))
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect::<Vec<_>>();
+
+    // stable, so algorithms with the same quality keep the configured order
+    candidates.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    for (algorithm, _) in candidates {
+        let variant = format!("{path}.{}", algorithm.extension());
+        match lookup(storage, &variant, method, headers).await {
+            Ok((variant_metadata, mut variant_validators)) => {
+                variant_validators.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+                variant_validators.insert(header::CONTENT_ENCODING, HeaderValue::from_static(algorithm.coding()));
+
+                return Ok((
+                    variant,
+                    Metadata {
+                        content_type: metadata.content_type,
+                        ..variant_metadata
+                    },
+                    variant_validators,
+                ));
+            }
+
+            Err(response) if response.status() == StatusCode::NOT_FOUND => {}
+            Err(response) => return Err(response),
+        }
+    }
+
+    Ok((path, metadata, validators))
+}
+
+/// Locates the object that should be served for `query`, returning its path alongside
+/// what [`lookup`] returns.
+///
+/// If no object exists at `query`, then the directory's index documents, its listing
+/// and the fallback document are tried in that order before giving up with a `404`.
+async fn locate(
+    storage: &Storage,
+    config: &Config,
+    query: &str,
+    method: &Method,
+    path: &CanonicalPath,
+    uri: &Uri,
+    headers: &HeaderMap,
+) -> Result<(String, Metadata, HeaderMap), Response<Body>> {
+    let not_found = match lookup(storage, query, method, headers).await {
+        Ok((metadata, validators)) => return Ok((query.to_owned(), metadata, validators)),
+        Err(response) if response.status() == StatusCode::NOT_FOUND => response,
+        Err(response) => return Err(response),
+    };
+
+    let directory = query.trim_end_matches('/');
+    for index in &config.server.index_files {
+        let candidate = match directory {
+            "" => index.clone(),
+            directory => format!("{directory}/{index}"),
+        };
+
+        // relative links in the index document need the directory's path to end with a
+        // slash to resolve correctly
+        if !path.is_directory() {
+            if let Ok(Some(Entry::File(_))) = storage.stat(&candidate).await {
+                return Err(autoindex::redirect_to_directory(path, uri));
+            }
+
+            continue;
+        }
+
+        match lookup(storage, &candidate, method, headers).await {
+            Ok((metadata, validators)) => return Ok((candidate, metadata, validators)),
+            Err(response) if response.status() == StatusCode::NOT_FOUND => {}
+            Err(response) => return Err(response),
+        }
+    }
+
+    if let Some(response) = autoindex::serve(storage, config, query, path, uri, headers).await {
+        return Err(response);
+    }
+
+    if let Some(ref fallback) = config.server.fallback &&
+        matches!(*method, Method::GET | Method::HEAD)
+    {
+        let fallback = fallback.trim_start_matches('/');
+        match lookup(storage, fallback, method, headers).await {
+            Ok((metadata, validators)) => return Ok((fallback.to_owned(), metadata, validators)),
+            Err(response) if response.status() == StatusCode::NOT_FOUND => {
+                warn!(fallback, "fallback document doesn't exist");
+            }
+
+            Err(response) => return Err(response),
+        }
+    }
+
+    Err(not_found)
+}
+
+/// Looks up the metadata of the object at `query` and evaluates the preconditions of the
+/// request against it.
+///
+/// On success, this returns the object's metadata and its validator headers (`ETag` and
+/// `Last-Modified`). Otherwise, the response that should be sent as-is is returned, like
+/// a `404 Not Found` or `304 Not Modified`.
+async fn lookup(
+    storage: &Storage,
+    query: &str,
+    method: &Method,
+    headers: &HeaderMap,
+) -> Result<(Metadata, HeaderMap), Response<Body>> {
+    let entry = storage
+        .stat(query)
         .await
         .inspect_err(|e| {
             error!(error = %e, query, "unable to perform lookup on query");
@@ -112,8 +538,10 @@ async fn query(
                     }
                 })),
             )
-        })?
-    else {
+                .into_response()
+        })?;
+
+    let Some(Entry::File(metadata)) = entry else {
         return Err((
             StatusCode::NOT_FOUND,
             Json(json!({
@@ -123,31 +551,265 @@ async fn query(
                     "query": query
                 }
             })),
-        ));
+        )
+            .into_response());
     };
 
-    match blob {
-        Blob::File(file) => {
-            let ct = file
-                .content_type
-                .unwrap_or_else(|| String::from(azalia::remi::fs::DEFAULT_CONTENT_TYPE));
+    let mut validators = HeaderMap::new();
+    if let Some(ref etag) = metadata.etag &&
+        let Ok(value) = HeaderValue::from_str(etag)
+    {
+        validators.insert(header::ETAG, value);
+    }
 
-            Ok(Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", ct)
-                .body(file.data.into())
-                .unwrap())
-        }
+    if let Some(last_modified) = metadata.last_modified {
+        validators.insert(
+            header::LAST_MODIFIED,
+            HeaderValue::from_str(&conditional::last_modified(last_modified)).unwrap(),
+        );
+    }
 
-        Blob::Directory(_) => Err((
-            StatusCode::NOT_FOUND,
+    match conditional::evaluate(method, headers, &metadata) {
+        Precondition::Passed => Ok((metadata, validators)),
+        Precondition::NotModified => Err((StatusCode::NOT_MODIFIED, validators).into_response()),
+        Precondition::Failed => Err((
+            StatusCode::PRECONDITION_FAILED,
+            validators,
             Json(json!({
-                "status": "not_found",
-                "message": "object was not found",
+                "status": "precondition_failed",
+                "message": "a precondition given in the request's headers failed",
                 "context": {
                     "query": query
                 }
             })),
-        )),
+        )
+            .into_response()),
+    }
+}
+
+async fn read(
+    storage: &Storage,
+    query: &str,
+    range: Option<std::ops::Range<u64>>,
+) -> Result<ByteStream, (StatusCode, Json<serde_json::Value>)> {
+    storage
+        .read(query, range)
+        .await
+        .inspect_err(|e| {
+            error!(error = %e, query, "unable to read object");
+            sentry::capture_error(e);
+        })
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "status": "failed",
+                    "message": "unable to read object! try again later maybe?",
+                    "context": {
+                        "query": query
+                    }
+                })),
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                Json(json!({
+                    "status": "not_found",
+                    "message": "object was not found",
+                    "context": {
+                        "query": query
+                    }
+                })),
+            )
+        })
+}
+
+/// Creates the body of a response from a stream of an object, counting the bytes that
+/// are sent as served.
+fn body(stream: ByteStream) -> Body {
+    Body::from_stream(stream.inspect(|chunk| {
+        if let Ok(chunk) = chunk {
+            metrics::served(chunk.len());
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::testing::{Object, from_config, get, oneshot, request, router, runtime, s3, send, text};
+    use axum::{
+        body::{self, Bytes},
+        http::{Method, StatusCode, header},
+    };
+    use futures_util::{StreamExt, stream};
+    use std::{collections::BTreeMap, fs, io, sync::Arc, time::Duration};
+    use tokio::sync::Notify;
+
+    const CHUNK: usize = 64 * 1024;
+
+    #[test]
+    fn test_head() {
+        let backend = s3(BTreeMap::from([("a.txt", Object::new(b"hello"))]));
+        let router = from_config(&backend.config);
+
+        runtime().block_on(async {
+            let head = oneshot(&router, Method::HEAD, "/a.txt", &[]).await;
+            assert_eq!(head.status(), StatusCode::OK);
+
+            // only the metadata is looked up, the contents are never read
+            assert_eq!(backend.requests(), ["HEAD /hazel//a.txt"]);
+
+            let get = oneshot(&router, Method::GET, "/a.txt", &[]).await;
+            assert_eq!(get.status(), StatusCode::OK);
+            for name in [
+                header::CONTENT_LENGTH,
+                header::CONTENT_TYPE,
+                header::ETAG,
+                header::LAST_MODIFIED,
+                header::ACCEPT_RANGES,
+            ] {
+                assert_eq!(head.headers().get(&name), get.headers().get(&name), "{name}");
+            }
+
+            assert_eq!(head.headers()[header::CONTENT_LENGTH], "5");
+            assert_eq!(head.headers()[header::ACCEPT_RANGES], "bytes");
+            assert!(body::to_bytes(head.into_body(), usize::MAX).await.unwrap().is_empty());
+            assert_eq!(body::to_bytes(get.into_body(), usize::MAX).await.unwrap(), "hello");
+        });
+    }
+
+    #[test]
+    fn test_streaming() {
+        // the rest of the object is only sent once the first chunk made it to the client,
+        // so this would never finish if it was buffered
+        let sent = Arc::new(Notify::new());
+        let object = Object {
+            size: (CHUNK * 64) as u64,
+            contents: Box::new({
+                let sent = sent.clone();
+                move || {
+                    let sent = sent.clone();
+                    let first = stream::once(async { Ok(Bytes::from(vec![b'a'; CHUNK])) });
+                    let rest = stream::once(async move { sent.notified().await })
+                        .flat_map(|()| stream::iter((1..64).map(|_| Ok(Bytes::from(vec![b'a'; CHUNK])))));
+
+                    first.chain(rest).boxed()
+                }
+            }),
+        };
+
+        let backend = s3(BTreeMap::from([("big.bin", object)]));
+        let router = from_config(&backend.config);
+
+        runtime().block_on(async {
+            let res =
+                tokio::time::timeout(Duration::from_secs(10), oneshot(&router, Method::GET, "/big.bin", &[]))
+                    .await
+                    .expect("the response waited for the whole object");
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers()[header::CONTENT_LENGTH], (CHUNK * 64).to_string().as_str());
+
+            let mut body = res.into_body().into_data_stream();
+            let mut received = 0;
+            while received < CHUNK {
+                received += body.next().await.unwrap().unwrap().len();
+            }
+
+            sent.notify_one();
+            while let Some(chunk) = body.next().await {
+                received += chunk.unwrap().len();
+            }
+
+            assert_eq!(received, CHUNK * 64);
+        });
+    }
+
+    #[test]
+    fn test_streaming_error() {
+        // the connection to the storage backend breaks once the first chunk made it to the
+        // client, so that the response has already started
+        let sent = Arc::new(Notify::new());
+        let object = Object {
+            size: (CHUNK * 64) as u64,
+            contents: Box::new({
+                let sent = sent.clone();
+                move || {
+                    let sent = sent.clone();
+                    let first = stream::once(async { Ok(Bytes::from(vec![b'a'; CHUNK])) });
+                    let error = stream::once(async move {
+                        sent.notified().await;
+                        Err(io::Error::other("the connection broke"))
+                    });
+
+                    first.chain(error).boxed()
+                }
+            }),
+        };
+
+        let backend = s3(BTreeMap::from([("broken.bin", object)]));
+        let router = from_config(&backend.config);
+
+        runtime().block_on(async {
+            let res = oneshot(&router, Method::GET, "/broken.bin", &[]).await;
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let mut body = res.into_body().into_data_stream();
+            let mut received = 0;
+            while received < CHUNK {
+                received += body.next().await.unwrap().unwrap().len();
+            }
+
+            // the response can't be turned into an error once it started, so it is cut off
+            // instead of ending as if the object was shorter
+            sent.notify_one();
+            assert!(body.next().await.unwrap().is_err());
+        });
+    }
+
+    #[test]
+    fn test_index_files() {
+        let (dir, router) = router("index-files", "[server]\nindex_files = [\"index.html\"]");
+        fs::write(dir.join("public/index.html"), "index").unwrap();
+
+        assert_eq!(text(send(&router, "/public/", &[])), "index");
+        assert_eq!(text(send(&router, "//%70ublic/", &[])), "index");
+
+        // relative links in the index document only resolve from the directory's path
+        for path in ["/public", "//public", "/%70ublic"] {
+            let res = send(&router, path, &[]);
+            assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT, "{path}");
+            assert_eq!(res.headers()[header::LOCATION], "/public/", "{path}");
+        }
+
+        assert_eq!(get(&router, "/private/", &[]), StatusCode::NOT_FOUND);
+        assert_eq!(get(&router, "/private", &[]), StatusCode::NOT_FOUND);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_fallback() {
+        let (dir, router) = router(
+            "fallback",
+            "[server]\nfallback = \"/index.html\"\n\n[[server.access_rules]]\naction = \"deny\"\nprefixes = [\"/private\"]",
+        );
+        fs::write(dir.join("index.html"), "app").unwrap();
+
+        assert_eq!(text(send(&router, "/public/a.txt", &[])), "hello");
+        assert_eq!(text(send(&router, "/settings/profile", &[])), "app");
+        assert_eq!(text(send(&router, "/public/missing.txt", &[])), "app");
+
+        let head = request(&router, Method::HEAD, "/settings/profile", &[]);
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(head.headers()[header::CONTENT_LENGTH], "3");
+
+        // paths that are denied are never answered with the fallback, however they're spelled
+        for path in ["/private/missing", "//private/missing", "/%70rivate/missing", "/private/a.txt"] {
+            assert_eq!(get(&router, path, &[]), StatusCode::FORBIDDEN, "{path}");
+        }
+
+        fs::remove_dir_all(dir).unwrap();
     }
 }
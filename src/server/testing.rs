@@ -0,0 +1,206 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for tests that send requests through the whole router.
+
+use crate::{config::Config, storage::Storage};
+use axum::{
+    Router,
+    body::{self, Body, Bytes},
+    extract::ConnectInfo,
+    http::{Method, Request, Response, StatusCode, Uri, header},
+    response::IntoResponse,
+};
+use futures_util::{
+    StreamExt,
+    stream::{self, BoxStream},
+};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tower::ServiceExt;
+
+/// Spellings of `/private/a.txt` that have to be treated the same as it.
+pub const SPELLINGS: [&str; 5] = [
+    "/private/a.txt",
+    "//private/a.txt",
+    "/%70rivate/a.txt",
+    "/private//a%2Etxt",
+    "/./private/a.txt",
+];
+
+/// Creates a directory with `private/a.txt` and `public/a.txt` in it, and the router
+/// that serves it with the configuration in `toml`. `$DIR` in `toml` is replaced with
+/// the directory's path.
+pub fn router(name: &str, toml: &str) -> (PathBuf, Router) {
+    let dir = std::env::temp_dir().join(format!("hazel-{name}-{}", std::process::id()));
+    for prefix in ["private", "public"] {
+        fs::create_dir_all(dir.join(prefix)).unwrap();
+        fs::write(dir.join(prefix).join("a.txt"), "hello").unwrap();
+    }
+
+    let directory = dir.display().to_string();
+    let toml = toml.replace("$DIR", &directory);
+    let router = from_config(&format!("[storage.filesystem]\ndirectory = {directory:?}\n\n{toml}"));
+
+    (dir, router)
+}
+
+/// Creates the router that serves the configuration in `toml`, which has to have a
+/// `[storage]` table.
+pub fn from_config(toml: &str) -> Router {
+    let config = toml::from_str::<Config>(toml).unwrap();
+    config.validate().unwrap();
+
+    let storage = Storage::new(config.storage.clone()).unwrap();
+
+    runtime().block_on(super::router(storage, &config)).unwrap()
+}
+
+pub fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+/// Sends a `GET` request for `path` from `127.0.0.1` with the given headers.
+pub fn get(router: &Router, path: &str, headers: &[(&str, &str)]) -> StatusCode {
+    send(router, path, headers).status()
+}
+
+/// Sends a `GET` request for `path` from `127.0.0.1` with the given headers.
+pub fn send(router: &Router, path: &str, headers: &[(&str, &str)]) -> Response<Body> {
+    request(router, Method::GET, path, headers)
+}
+
+/// Sends a request for `path` from `127.0.0.1` with the given headers.
+pub fn request(router: &Router, method: Method, path: &str, headers: &[(&str, &str)]) -> Response<Body> {
+    runtime().block_on(oneshot(router, method, path, headers))
+}
+
+/// Sends a request like [`request`], for tests that have to stay on one runtime (like
+/// the ones whose storage backend keeps connections open).
+pub async fn oneshot(router: &Router, method: Method, path: &str, headers: &[(&str, &str)]) -> Response<Body> {
+    let mut req = Request::builder().method(method).uri(path);
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+
+    let mut req = req.body(Body::empty()).unwrap();
+    req.extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))));
+
+    router.clone().oneshot(req).await.unwrap()
+}
+
+/// Reads the whole body of `response` as a string.
+pub fn text(response: Response<Body>) -> String {
+    let body = runtime()
+        .block_on(body::to_bytes(response.into_body(), usize::MAX))
+        .unwrap();
+
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+/// An object that is served by [`s3`].
+pub struct Object {
+    /// Size of the object that is reported, which its contents can fall short of.
+    pub size: u64,
+
+    /// Creates the contents of the object for each request that reads it. An error ends
+    /// the response early, like a connection that breaks.
+    pub contents: Box<dyn Fn() -> BoxStream<'static, io::Result<Bytes>> + Send + Sync>,
+}
+
+impl Object {
+    /// An object that has `contents`.
+    pub fn new(contents: &'static [u8]) -> Object {
+        Object {
+            size: contents.len() as u64,
+            contents: Box::new(move || stream::once(async move { Ok(Bytes::from_static(contents)) }).boxed()),
+        }
+    }
+}
+
+/// A local server that serves `objects` from the `hazel` bucket like S3 would.
+pub struct S3 {
+    /// The `[storage.s3]` table that points to the server.
+    pub config: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl S3 {
+    /// Returns the method and path of every request that the server got so far, like
+    /// `HEAD /hazel//a.txt`.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Starts an [`S3`] server that serves `objects`, by their path.
+pub fn s3(objects: BTreeMap<&'static str, Object>) -> S3 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+
+    let config = format!(
+        "[storage.s3]\nbucket = \"hazel\"\nregion = \"us-east-1\"\nendpoint = \"http://{}\"\nenforce_path_access_style = true\naccess_key_id = \"hazel\"\nsecret_access_key = \"hazel\"",
+        listener.local_addr().unwrap()
+    );
+
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let objects = Arc::new(objects);
+    let handler = {
+        let requests = requests.clone();
+        move |method: Method, uri: Uri| async move {
+            requests.lock().unwrap().push(format!("{method} {}", uri.path()));
+
+            // keys are looked up as `/{path}`, see `storage::s3_key`
+            let key = uri
+                .path()
+                .strip_prefix("/hazel/")
+                .map(|key| key.trim_start_matches('/'));
+            let Some(object) = key.and_then(|key| objects.get(key)) else {
+                return StatusCode::NOT_FOUND.into_response();
+            };
+
+            let body = match method {
+                Method::HEAD => Body::empty(),
+                _ => Body::from_stream((object.contents)()),
+            };
+
+            Response::builder()
+                .header(header::CONTENT_LENGTH, object.size)
+                .header(header::CONTENT_TYPE, "text/plain")
+                .header(header::ETAG, "\"1\"")
+                .header(header::LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT")
+                .body(body)
+                .unwrap()
+        }
+    };
+
+    std::thread::spawn(move || {
+        runtime().block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            axum::serve(listener, Router::new().fallback(handler)).await.unwrap();
+        })
+    });
+
+    S3 { config, requests }
+}
@@ -0,0 +1,363 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signed URLs that grant temporary access to objects in prefixes that require them.
+//!
+//! A signed URL carries a [`Grant`] and an HMAC-SHA256 signature over it in its query
+//! string, like:
+//!
+//! ```text
+//! /private/report.pdf?X-Hazel-Expires=1735689600&X-Hazel-Key=2025-01&X-Hazel-Signature=...
+//! ```
+//!
+//! The signature covers the request's method and path, when the URL expires, and the
+//! optional client IP and `Range` constraints. Any other query parameters aren't covered.
+
+use axum::http::Method;
+use hmac::{Hmac, Mac};
+use ipnet::IpNet;
+use sha2::Sha256;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    net::IpAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Query parameter of when the URL expires, in seconds since the Unix epoch.
+pub const EXPIRES: &str = "X-Hazel-Expires";
+
+/// Query parameter of the ID of the key that the URL was signed with.
+pub const KEY: &str = "X-Hazel-Key";
+
+/// Query parameter of the hex-encoded signature.
+pub const SIGNATURE: &str = "X-Hazel-Signature";
+
+/// Query parameter of the IP address or CIDR range that the client has to connect from.
+pub const IP: &str = "X-Hazel-IP";
+
+/// Query parameter of the exact `Range` header that the request has to be sent with.
+pub const RANGE: &str = "X-Hazel-Range";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a signed URL grants access to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    /// The method that the URL can be requested with. `HEAD` requests are allowed by
+    /// URLs that were signed for `GET`.
+    pub method: Method,
+
+    /// The [`CanonicalPath`] of the object, which is what requests for any spelling of
+    /// the path are verified against.
+    ///
+    /// [`CanonicalPath`]: crate::server::canonical::CanonicalPath
+    pub path: String,
+
+    /// When the URL expires.
+    pub expires: SystemTime,
+
+    /// The IP address or CIDR range that the client has to connect from.
+    pub ip: Option<IpNet>,
+
+    /// The exact `Range` header that the request has to be sent with, like `bytes=0-1023`.
+    pub range: Option<String>,
+}
+
+impl Grant {
+    /// Creates a [`Grant`] to `GET` the object at `path` for the given duration.
+    pub fn new<P: Into<String>>(path: P, expires_in: Duration) -> Grant {
+        Grant {
+            method: Method::GET,
+            path: path.into(),
+            expires: SystemTime::now() + expires_in,
+            ip: None,
+            range: None,
+        }
+    }
+
+    /// Signs this grant with the key `key_id`, returning the query string (without the
+    /// leading `?`) that needs to be added to the URL.
+    pub fn sign(&self, key_id: &str, secret: &[u8]) -> String {
+        let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+        serializer
+            .append_pair(EXPIRES, &unix_seconds(self.expires).to_string())
+            .append_pair(KEY, key_id);
+
+        if let Some(ref ip) = self.ip {
+            serializer.append_pair(IP, &ip.to_string());
+        }
+
+        if let Some(ref range) = self.range {
+            serializer.append_pair(RANGE, range);
+        }
+
+        serializer.append_pair(SIGNATURE, &hex::encode(self.mac(secret).finalize().into_bytes()));
+        serializer.finish()
+    }
+
+    /// The string that is signed. Each part is prefixed with its length, so that parts
+    /// can't be shifted into each other whatever they contain.
+    fn mac(&self, secret: &[u8]) -> HmacSha256 {
+        let method = match self.method {
+            Method::HEAD => Method::GET,
+            ref method => method.clone(),
+        };
+
+        let mut canonical = String::new();
+        for part in [
+            method.as_str(),
+            &self.path,
+            &unix_seconds(self.expires).to_string(),
+            &self.ip.map(|ip| ip.to_string()).unwrap_or_default(),
+            self.range.as_deref().unwrap_or_default(),
+        ] {
+            canonical.push_str(&format!("{}:{part}\n", part.len()));
+        }
+
+        let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+        mac.update(canonical.as_bytes());
+        mac
+    }
+}
+
+/// Reasons why a signed URL was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The URL isn't signed.
+    Missing,
+
+    /// A query parameter couldn't be parsed.
+    Malformed(&'static str),
+
+    /// The URL was signed with a key that isn't configured (anymore).
+    UnknownKey,
+
+    /// The signature doesn't match.
+    InvalidSignature,
+
+    /// The URL has expired.
+    Expired,
+
+    /// The client isn't connecting from the IP that the URL was signed for.
+    IpMismatch,
+
+    /// The request's `Range` header isn't the one that the URL was signed for.
+    RangeMismatch,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Missing => f.write_str("a signed url is required to access this object"),
+            Error::Malformed(param) => write!(f, "query parameter `{param}` is malformed"),
+            Error::UnknownKey => f.write_str("signed url was signed with an unknown key"),
+            Error::InvalidSignature => f.write_str("signature of the signed url is invalid"),
+            Error::Expired => f.write_str("signed url has expired"),
+            Error::IpMismatch => f.write_str("signed url can't be used from this ip address"),
+            Error::RangeMismatch => f.write_str("signed url can't be used with this range"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Verifies the signed URL of a request with its `method`, canonical `path` and `query`
+/// string.
+///
+/// `client` is the IP address that the request came from (if known), and `range` is its
+/// `Range` header. The signature is verified against the key with the ID that the URL
+/// names out of `keys`.
+pub fn verify(
+    keys: &BTreeMap<String, String>,
+    method: &Method,
+    path: &str,
+    query: Option<&str>,
+    client: Option<IpAddr>,
+    range: Option<&str>,
+    now: SystemTime,
+) -> Result<(), Error> {
+    let mut expires = None;
+    let mut key = None;
+    let mut signature = None;
+    let mut ip = None;
+    let mut signed_range = None;
+
+    for (name, value) in url::form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        match &*name {
+            EXPIRES => expires = Some(value),
+            KEY => key = Some(value),
+            SIGNATURE => signature = Some(value),
+            IP => ip = Some(value),
+            RANGE => signed_range = Some(value),
+            _ => {}
+        }
+    }
+
+    let (Some(expires), Some(key), Some(signature)) = (expires, key, signature) else {
+        return Err(Error::Missing);
+    };
+
+    let expires = expires
+        .parse::<u64>()
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+        .map_err(|_| Error::Malformed(EXPIRES))?;
+
+    let signature = hex::decode(&*signature).map_err(|_| Error::Malformed(SIGNATURE))?;
+    let ip = ip.map(|ip| parse_ip(&ip).ok_or(Error::Malformed(IP))).transpose()?;
+
+    let secret = keys.get(&*key).ok_or(Error::UnknownKey)?;
+    let grant = Grant {
+        method: method.clone(),
+        path: path.to_owned(),
+        expires,
+        ip,
+        range: signed_range.map(|range| range.into_owned()),
+    };
+
+    // the signature is checked first (in constant time) so that nothing else about the
+    // grant is revealed to someone that forged it
+    grant
+        .mac(secret.as_bytes())
+        .verify_slice(&signature)
+        .map_err(|_| Error::InvalidSignature)?;
+
+    if now > grant.expires {
+        return Err(Error::Expired);
+    }
+
+    if let Some(ip) = grant.ip &&
+        !client.is_some_and(|client| ip.contains(&client))
+    {
+        return Err(Error::IpMismatch);
+    }
+
+    if let Some(ref signed) = grant.range &&
+        range != Some(signed.as_str())
+    {
+        return Err(Error::RangeMismatch);
+    }
+
+    Ok(())
+}
+
+/// Parses an IP address or CIDR range. A plain IP address is a range of only itself.
+pub fn parse_ip(input: &str) -> Option<IpNet> {
+    input
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| input.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Grant, parse_ip, verify};
+    use axum::http::Method;
+    use std::{
+        collections::BTreeMap,
+        time::{Duration, SystemTime},
+    };
+
+    fn keys() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            (String::from("old"), String::from("hunter2")),
+            (String::from("new"), String::from("correct horse battery staple")),
+        ])
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let now = SystemTime::now();
+        let mut grant = Grant::new("/private/a b.txt", Duration::from_secs(60));
+        grant.ip = parse_ip("10.0.0.0/8");
+
+        let query = grant.sign("old", b"hunter2");
+        let client = "10.1.2.3".parse().ok();
+        let path = "/private/a b.txt";
+
+        assert_eq!(
+            verify(&keys(), &Method::GET, path, Some(&query), client, None, now),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&keys(), &Method::HEAD, path, Some(&query), client, None, now),
+            Ok(())
+        );
+
+        assert_eq!(
+            verify(
+                &keys(),
+                &Method::GET,
+                "/private/other.txt",
+                Some(&query),
+                client,
+                None,
+                now
+            ),
+            Err(Error::InvalidSignature)
+        );
+
+        assert_eq!(
+            verify(
+                &keys(),
+                &Method::GET,
+                path,
+                Some(&query),
+                "192.168.1.1".parse().ok(),
+                None,
+                now
+            ),
+            Err(Error::IpMismatch)
+        );
+
+        assert_eq!(
+            verify(
+                &keys(),
+                &Method::GET,
+                path,
+                Some(&query),
+                client,
+                None,
+                now + Duration::from_secs(120)
+            ),
+            Err(Error::Expired)
+        );
+
+        assert_eq!(
+            verify(&keys(), &Method::GET, path, Some("a=b"), client, None, now),
+            Err(Error::Missing)
+        );
+    }
+
+    #[test]
+    fn test_rotated_keys() {
+        let now = SystemTime::now();
+        let query = Grant::new("/a", Duration::from_secs(60)).sign("new", b"correct horse battery staple");
+        assert_eq!(
+            verify(&keys(), &Method::GET, "/a", Some(&query), None, None, now),
+            Ok(())
+        );
+
+        let query = Grant::new("/a", Duration::from_secs(60)).sign("removed", b"whatever");
+        assert_eq!(
+            verify(&keys(), &Method::GET, "/a", Some(&query), None, None, now),
+            Err(Error::UnknownKey)
+        );
+    }
+}
@@ -0,0 +1,1014 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod cache;
+pub mod coalescing;
+pub mod disk_cache;
+
+use crate::{
+    config::{server, storage},
+    metrics,
+};
+use axum::body::Bytes;
+use azalia::remi::{
+    self, StorageService,
+    azure::core::{
+        Context, CustomHeaders, StatusCode as AzureStatusCode,
+        headers::{CLIENT_REQUEST_ID, Headers},
+        storage::blobs::prelude::ContainerClient,
+    },
+    core::StorageService as _,
+    s3::aws::s3::{Client, config::http::HttpRequest},
+};
+use futures_util::{Stream, StreamExt, TryStreamExt, future, stream};
+use lru::LruCache;
+use std::{
+    io::{self, SeekFrom},
+    num::{NonZeroU32, NonZeroUsize},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+/// Error type that all storage operations can return.
+pub type Error = remi::Error;
+
+/// A stream of the contents of a file.
+pub type ByteStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+/// Size of the buffer that is used when streaming files, which is the most amount of
+/// a file that is held in memory at once per stream.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Amount of bytes that are read from the start of a file to detect its content type
+/// when it can't be guessed from its extension.
+const SNIFF_LENGTH: u64 = 8192;
+
+/// Amount of directories on the local filesystem whose entries are kept for listing
+/// them, see [`Storage::names`].
+const LISTINGS_CAPACITY: NonZeroUsize = NonZeroUsize::new(256).unwrap();
+
+/// How long a directory has to stay unmodified before its entries are kept.
+const LISTINGS_SETTLE: Duration = Duration::from_secs(2);
+
+/// The sorted names of a directory's entries, alongside its modification time when
+/// they were read.
+type Names = (SystemTime, Arc<[String]>);
+
+/// Metadata about a file that was located in the storage backend.
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    /// The `Content-Type` of the file.
+    pub content_type: String,
+
+    /// When this file was last modified, if the storage backend knows.
+    pub last_modified: Option<SystemTime>,
+
+    /// The entity tag of this file, including its surrounding quotes. This is given
+    /// by the storage backend, or derived from the file's metadata on the local
+    /// filesystem.
+    pub etag: Option<String>,
+
+    /// The length of this file in bytes.
+    pub size: u64,
+}
+
+/// Represents an entry that was located in the storage backend.
+#[derive(Debug, Clone)]
+pub enum Entry {
+    /// A file that can be read with [`Storage::read`].
+    File(Metadata),
+
+    /// A directory.
+    Directory,
+}
+
+/// An entry in a directory that was listed with [`Storage::list`].
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    /// Name of this entry relative to the directory it is in. Directories don't
+    /// include a trailing slash.
+    pub name: String,
+
+    /// Whether if this entry is a directory.
+    pub is_dir: bool,
+
+    /// The length of this file in bytes, or `None` for directories.
+    pub size: Option<u64>,
+
+    /// When this entry was last modified, if the storage backend knows.
+    pub last_modified: Option<SystemTime>,
+}
+
+/// A page of entries from a directory that was listed with [`Storage::list`].
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+    /// The entries in this page, sorted by name.
+    pub entries: Vec<DirEntry>,
+
+    /// Opaque cursor that can be given to [`Storage::list`] to get the next page, or
+    /// `None` if this is the last page.
+    pub next: Option<String>,
+}
+
+/// Represents the storage backend that Hazel proxies objects from.
+///
+/// This wraps a `remi` [`StorageService`] but talks to each backend directly when it
+/// needs something that `remi` doesn't provide, like metadata-only lookups or reading
+/// a range of bytes from an object.
+#[derive(Clone)]
+pub struct Storage {
+    service: StorageService,
+    backend: Backend,
+
+    /// ID of the request that this is used for, which is sent along with requests to
+    /// S3 and Azure.
+    request_id: Option<Arc<str>>,
+
+    /// The cache that objects are served from before asking the storage backend.
+    cache: Option<cache::Cache>,
+
+    /// The on-disk cache that objects are served from after [`Storage::cache`].
+    disk_cache: Option<disk_cache::DiskCache>,
+
+    /// Deduplicates concurrent calls to the storage backend.
+    coalescer: Option<Arc<coalescing::Coalescer>>,
+}
+
+/// The caches that are enabled in `[server]`, which are shared by every storage backend,
+/// and the configuration of request coalescing in front of each one alongside the
+/// budget that they share.
+#[derive(Clone, Default)]
+pub struct Caches {
+    memory: Option<cache::Cache>,
+    disk: Option<disk_cache::DiskCache>,
+    coalescing: Option<(server::coalescing::Config, coalescing::Budget)>,
+}
+
+impl Caches {
+    /// Creates the caches from `[server.cache]` and `[server.disk_cache]`, and reads
+    /// `[server.coalescing]`.
+    pub fn new(config: &server::Config) -> eyre::Result<Caches> {
+        Ok(Caches {
+            memory: config.cache.as_ref().map(cache::Cache::new),
+            disk: config
+                .disk_cache
+                .as_ref()
+                .map(disk_cache::DiskCache::open)
+                .transpose()?,
+            coalescing: config
+                .coalescing
+                .clone()
+                .map(|config| (config.clone(), coalescing::Budget::new(&config))),
+        })
+    }
+
+    /// Returns `storage` with every cache in front of it, and its calls to the storage
+    /// backend coalesced if that is enabled.
+    pub fn attach(&self, mut storage: Storage) -> Storage {
+        if let Some((ref config, ref budget)) = self.coalescing {
+            storage.coalescer = Some(Arc::new(coalescing::Coalescer::new(config, budget.clone())));
+        }
+
+        if let Some(ref cache) = self.memory {
+            storage = storage.with_cache(cache);
+        }
+
+        if let Some(ref disk) = self.disk {
+            storage = storage.with_disk_cache(disk);
+        }
+
+        storage
+    }
+}
+
+#[derive(Clone)]
+enum Backend {
+    Filesystem {
+        directory: PathBuf,
+        listings: Arc<Mutex<LruCache<PathBuf, Names>>>,
+    },
+
+    S3 {
+        client: Client,
+        bucket: String,
+        prefix: Option<String>,
+    },
+
+    Azure(ContainerClient),
+}
+
+impl Storage {
+    /// Creates a new [`Storage`] from the `[storage]` table.
+    pub fn new(config: storage::Config) -> eyre::Result<Storage> {
+        match config {
+            storage::Config::Filesystem(fs) => Ok(Storage {
+                backend: Backend::Filesystem {
+                    directory: fs.directory.clone(),
+                    listings: Arc::new(Mutex::new(LruCache::new(LISTINGS_CAPACITY))),
+                },
+
+                service: StorageService::Filesystem(remi::fs::StorageService::with_config(fs)),
+                request_id: None,
+                cache: None,
+                disk_cache: None,
+                coalescer: None,
+            }),
+
+            storage::Config::S3(s3) => Ok(Storage {
+                backend: Backend::S3 {
+                    client: Client::from_conf(s3.clone().into()),
+                    bucket: s3.bucket.clone(),
+                    prefix: s3.prefix.clone(),
+                },
+
+                service: StorageService::S3(remi::s3::StorageService::new(s3)),
+                request_id: None,
+                cache: None,
+                disk_cache: None,
+                coalescer: None,
+            }),
+
+            storage::Config::Azure(azure) => {
+                let service = remi::azure::StorageService::new(azure)?;
+                Ok(Storage {
+                    backend: Backend::Azure((*service).clone()),
+                    service: StorageService::Azure(service),
+                    request_id: None,
+                    cache: None,
+                    disk_cache: None,
+                    coalescer: None,
+                })
+            }
+        }
+    }
+
+    /// Returns a [`Storage`] whose requests to the storage backend are tagged with the
+    /// ID of the request that it is used for: it is appended to the `User-Agent` for
+    /// S3, and sent as the `x-ms-client-request-id` header for Azure.
+    pub fn with_request_id(&self, id: &str) -> Storage {
+        Storage {
+            request_id: Some(Arc::from(id)),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a [`Storage`] that caches the objects that it looks up and reads in its
+    /// own scope of `cache`.
+    pub fn with_cache(&self, cache: &cache::Cache) -> Storage {
+        Storage {
+            cache: Some(cache.scope()),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a [`Storage`] that caches the objects that it reads in `cache`. Objects
+    /// from the local filesystem are already on disk, so they aren't cached there.
+    pub fn with_disk_cache(&self, cache: &disk_cache::DiskCache) -> Storage {
+        let origin = match self.backend {
+            Backend::Filesystem { .. } => return self.clone(),
+            Backend::S3 {
+                ref bucket, ref prefix, ..
+            } => format!("s3://{bucket}/{}", prefix.as_deref().unwrap_or_default()),
+
+            Backend::Azure(ref container) => container
+                .url()
+                .map(String::from)
+                .unwrap_or_else(|_| format!("azure://{}", container.container_name())),
+        };
+
+        Storage {
+            disk_cache: Some(cache.scope(&origin)),
+            ..self.clone()
+        }
+    }
+
+    /// Whether if the contents of the object at `path` are cached, or `None` if this
+    /// doesn't have a cache.
+    pub fn is_cached(&self, path: &str) -> Option<bool> {
+        if self.cache.is_none() && self.disk_cache.is_none() {
+            return None;
+        }
+
+        Some(normalize(path).is_some_and(|path| {
+            self.cache.as_ref().is_some_and(|cache| cache.contains(&path)) ||
+                self.disk_cache.as_ref().is_some_and(|cache| cache.contains(&path))
+        }))
+    }
+
+    /// Returns a function that appends the request ID to the `User-Agent` of a request
+    /// to S3.
+    fn s3_user_agent(&self) -> impl Fn(&mut HttpRequest) + Send + Sync + 'static {
+        let request_id = self.request_id.clone();
+        move |req| {
+            let Some(ref id) = request_id else {
+                return;
+            };
+
+            let user_agent = match req.headers().get("user-agent") {
+                Some(ua) => format!("{ua} hazel-request-id/{id}"),
+                None => format!("hazel-request-id/{id}"),
+            };
+
+            req.headers_mut().insert("user-agent", user_agent);
+        }
+    }
+
+    /// Returns the [`Context`] that requests to Azure are sent with, which has the
+    /// request ID as the `x-ms-client-request-id` header.
+    fn azure_context(&self) -> Context {
+        let mut context = Context::new();
+        if let Some(ref id) = self.request_id {
+            let mut headers = Headers::new();
+            headers.insert(CLIENT_REQUEST_ID, id.to_string());
+            context.insert(CustomHeaders::from(headers));
+        }
+
+        context
+    }
+
+    /// Returns the name of the storage backend, as used in metrics.
+    pub const fn backend_name(&self) -> &'static str {
+        match self.backend {
+            Backend::Filesystem { .. } => "filesystem",
+            Backend::S3 { .. } => "s3",
+            Backend::Azure(_) => "azure",
+        }
+    }
+
+    /// Runs `fut` and records how long the `operation` took and whether it failed.
+    async fn observe<T>(
+        &self,
+        operation: &'static str,
+        fut: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let start = Instant::now();
+        let result = fut.await;
+        metrics::storage_operation(self.backend_name(), operation, start.elapsed(), result.is_err());
+
+        result
+    }
+
+    /// Initializes the underlying storage service, like creating the directory,
+    /// bucket or container if it doesn't exist.
+    pub async fn init(&self) -> Result<(), Error> {
+        self.service.init().await
+    }
+
+    /// Returns the [`Entry`] that is located at `path` without reading its contents,
+    /// or `None` if nothing exists there.
+    ///
+    /// If this has a cache, then the cached metadata is returned as long as it's within
+    /// the TTL, without asking the storage backend.
+    pub async fn stat(&self, path: &str) -> Result<Option<Entry>, Error> {
+        if self.cache.is_none() && self.disk_cache.is_none() && self.coalescer.is_none() {
+            return self.observe("stat", self.stat_from_backend(path)).await;
+        }
+
+        let Some(key) = normalize(path) else {
+            return Ok(None);
+        };
+
+        if let Some(ref cache) = self.cache &&
+            let Some(metadata) = cache.metadata(&key)
+        {
+            return Ok(Some(Entry::File(metadata)));
+        }
+
+        let entry = match self.coalescer {
+            Some(ref coalescer) => {
+                let storage = self.clone();
+                let path = key.clone();
+                coalescer
+                    .stat(&key, async move {
+                        storage.observe("stat", storage.stat_from_backend(&path)).await
+                    })
+                    .await?
+            }
+
+            None => self.observe("stat", self.stat_from_backend(path)).await?,
+        };
+
+        let metadata = match entry {
+            Some(Entry::File(ref metadata)) => Some(metadata),
+            _ => None,
+        };
+
+        if let Some(ref cache) = self.cache {
+            cache.validate(&key, metadata);
+        }
+
+        if let Some(ref cache) = self.disk_cache {
+            cache.validate(&key, metadata);
+        }
+
+        Ok(entry)
+    }
+
+    async fn stat_from_backend(&self, path: &str) -> Result<Option<Entry>, Error> {
+        let Some(path) = normalize(path) else {
+            return Ok(None);
+        };
+
+        match self.backend {
+            Backend::Filesystem { ref directory, .. } => {
+                let path = directory.join(&path);
+                let metadata = match tokio::fs::metadata(&path).await {
+                    Ok(metadata) => metadata,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                };
+
+                if metadata.is_dir() {
+                    return Ok(Some(Entry::Directory));
+                }
+
+                let content_type = match guess_content_type(&path) {
+                    Some(ct) => ct,
+                    None => sniff_content_type(&path).await?,
+                };
+
+                Ok(Some(Entry::File(Metadata {
+                    content_type,
+                    last_modified: metadata.modified().ok(),
+                    etag: file_etag(&metadata),
+                    size: metadata.len(),
+                })))
+            }
+
+            Backend::S3 {
+                ref client,
+                ref bucket,
+                ref prefix,
+            } => {
+                let key = s3_key(prefix.as_deref(), &path);
+                let output = match client
+                    .head_object()
+                    .bucket(bucket)
+                    .key(&key)
+                    .customize()
+                    .mutate_request(self.s3_user_agent())
+                    .send()
+                    .await
+                {
+                    Ok(output) => output,
+                    Err(e) => {
+                        let err = e.into_service_error();
+                        if err.is_not_found() {
+                            return Ok(None);
+                        }
+
+                        return Err(remi::s3::Error::from(err).into());
+                    }
+                };
+
+                Ok(Some(Entry::File(Metadata {
+                    content_type: output
+                        .content_type()
+                        .map(String::from)
+                        .or_else(|| guess_content_type(Path::new(&key)))
+                        .unwrap_or_else(|| String::from(remi::fs::DEFAULT_CONTENT_TYPE)),
+
+                    last_modified: output.last_modified().and_then(|dt| SystemTime::try_from(*dt).ok()),
+                    etag: output.e_tag().map(quote_etag),
+                    size: output
+                        .content_length()
+                        .unwrap_or_default()
+                        .try_into()
+                        .unwrap_or_default(),
+                })))
+            }
+
+            Backend::Azure(ref container) => {
+                let name = azure_blob_name(&path);
+                let props = match container
+                    .blob_client(&name)
+                    .get_properties()
+                    .context(self.azure_context())
+                    .await
+                {
+                    Ok(props) => props,
+                    Err(e) if e.as_http_error().map(|e| e.status()) == Some(AzureStatusCode::NotFound) => {
+                        return Ok(None);
+                    }
+
+                    Err(e) => return Err(e.into()),
+                };
+
+                let properties = props.blob.properties;
+                Ok(Some(Entry::File(Metadata {
+                    content_type: match properties.content_type {
+                        ct if ct.is_empty() => guess_content_type(Path::new(&name))
+                            .unwrap_or_else(|| String::from(remi::fs::DEFAULT_CONTENT_TYPE)),
+
+                        ct => ct,
+                    },
+
+                    last_modified: Some(properties.last_modified.into()),
+                    etag: Some(quote_etag(properties.etag.as_ref())),
+                    size: properties.content_length,
+                })))
+            }
+        }
+    }
+
+    /// Opens a stream of the contents of the file at `path`. If `range` is given, then
+    /// only that range of bytes is read from the storage backend.
+    ///
+    /// The stream is read in chunks as it is polled, so only a bounded amount of the
+    /// file is held in memory at once regardless of how large it is.
+    ///
+    /// The caller is expected to check that `range` is within the length of the file
+    /// from [`Storage::stat`] beforehand.
+    ///
+    /// If this has a cache, then the cached contents are streamed instead, and the full
+    /// contents of objects that were looked up are cached once they're read.
+    pub async fn read(&self, path: &str, range: Option<Range<u64>>) -> Result<Option<ByteStream>, Error> {
+        if self.cache.is_none() && self.disk_cache.is_none() && self.coalescer.is_none() {
+            return self.observe("read", self.read_from_backend(path, range)).await;
+        }
+
+        let Some(key) = normalize(path) else {
+            return Ok(None);
+        };
+
+        if let Some(ref cache) = self.cache &&
+            let Some(stream) = cache.read(&key, range.clone())
+        {
+            return Ok(Some(stream));
+        }
+
+        let cached = match self.disk_cache {
+            Some(ref cache) => cache.read(&key, range.clone()).await,
+            None => None,
+        };
+
+        let mut stream = match cached {
+            Some(stream) => Some(stream),
+            None => {
+                let stream = self.read_coalesced(&key, range.clone()).await?;
+                match (stream, &self.disk_cache) {
+                    (Some(stream), Some(cache)) if range.is_none() => Some(cache.fill_from(&key, stream)),
+                    (stream, _) => stream,
+                }
+            }
+        };
+
+        if let Some(ref cache) = self.cache &&
+            range.is_none() &&
+            let Some(size) = cache.wants(&key)
+        {
+            stream = stream.map(|stream| cache.fill_from(&key, size, stream));
+        }
+
+        Ok(stream)
+    }
+
+    /// Reads from the storage backend, or joins the read of the full contents of the
+    /// object that is already in flight if there is one.
+    async fn read_coalesced(&self, key: &str, range: Option<Range<u64>>) -> Result<Option<ByteStream>, Error> {
+        if range.is_none() &&
+            let Some(ref coalescer) = self.coalescer
+        {
+            let storage = self.clone();
+            let path = key.to_owned();
+            let open = async move { storage.observe("read", storage.read_from_backend(&path, None)).await };
+            if let Some(result) = coalescer.read(key, open).await {
+                return result;
+            }
+        }
+
+        self.observe("read", self.read_from_backend(key, range)).await
+    }
+
+    async fn read_from_backend(&self, path: &str, range: Option<Range<u64>>) -> Result<Option<ByteStream>, Error> {
+        let Some(path) = normalize(path) else {
+            return Ok(None);
+        };
+
+        match self.backend {
+            Backend::Filesystem { ref directory, .. } => {
+                let mut file = match tokio::fs::File::open(directory.join(&path)).await {
+                    Ok(file) => file,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                    Err(e) => return Err(e.into()),
+                };
+
+                match range {
+                    Some(range) => {
+                        file.seek(SeekFrom::Start(range.start)).await?;
+                        Ok(Some(Box::pin(ReaderStream::with_capacity(
+                            file.take(range.end - range.start),
+                            CHUNK_SIZE,
+                        ))))
+                    }
+
+                    None => Ok(Some(Box::pin(ReaderStream::with_capacity(file, CHUNK_SIZE)))),
+                }
+            }
+
+            Backend::S3 {
+                ref client,
+                ref bucket,
+                ref prefix,
+            } => {
+                let mut request = client.get_object().bucket(bucket).key(s3_key(prefix.as_deref(), &path));
+                if let Some(range) = range {
+                    request = request.range(format!("bytes={}-{}", range.start, range.end - 1));
+                }
+
+                let output = match request.customize().mutate_request(self.s3_user_agent()).send().await {
+                    Ok(output) => output,
+                    Err(e) => {
+                        let err = e.into_service_error();
+                        if err.is_no_such_key() {
+                            return Ok(None);
+                        }
+
+                        return Err(remi::s3::Error::from(err).into());
+                    }
+                };
+
+                Ok(Some(Box::pin(ReaderStream::with_capacity(
+                    output.body.into_async_read(),
+                    CHUNK_SIZE,
+                ))))
+            }
+
+            Backend::Azure(ref container) => {
+                let mut request = container
+                    .blob_client(azure_blob_name(&path))
+                    .get()
+                    .context(self.azure_context());
+
+                if let Some(range) = range {
+                    request = request.range(range);
+                }
+
+                // The blob is fetched in chunks by issuing a request for each of them, so we
+                // only need to wait for the first one to know if the blob exists.
+                let mut pages = request.into_stream();
+                let first = match pages.next().await {
+                    Some(Ok(page)) => page,
+                    Some(Err(e)) if e.as_http_error().map(|e| e.status()) == Some(AzureStatusCode::NotFound) => {
+                        return Ok(None);
+                    }
+
+                    Some(Err(e)) => return Err(e.into()),
+                    None => return Ok(Some(Box::pin(stream::empty()))),
+                };
+
+                let stream = stream::once(future::ready(Ok(first)))
+                    .chain(pages)
+                    .map_ok(|page| page.data)
+                    .try_flatten()
+                    .map_err(io::Error::other);
+
+                Ok(Some(Box::pin(stream)))
+            }
+        }
+    }
+
+    /// Lists a page of up to `limit` entries in the directory at `path`, starting after
+    /// `cursor` (which is given by the previous [`Listing::next`]). `None` is returned
+    /// if there is no directory at `path`.
+    ///
+    /// Since S3 and Azure don't have real directories, a directory there exists if any
+    /// object's key starts with it.
+    pub async fn list(&self, path: &str, cursor: Option<&str>, limit: usize) -> Result<Option<Listing>, Error> {
+        self.observe("list", self.list_from_backend(path, cursor, limit)).await
+    }
+
+    async fn list_from_backend(
+        &self,
+        path: &str,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<Option<Listing>, Error> {
+        let Some(path) = normalize(path) else {
+            return Ok(None);
+        };
+
+        let limit = limit.max(1);
+        match self.backend {
+            Backend::Filesystem { ref directory, .. } => {
+                let directory = directory.join(&path);
+                let Some(names) = self.names(&directory).await? else {
+                    return Ok(None);
+                };
+
+                let start = match cursor {
+                    Some(cursor) => names.partition_point(|name| name.as_str() <= cursor),
+                    None => 0,
+                };
+
+                // only the entries on this page are stat'd
+                let mut entries = Vec::new();
+                let mut next = None;
+                for name in &names[start..] {
+                    if entries.len() == limit {
+                        next = entries.last().map(|entry: &DirEntry| entry.name.clone());
+                        break;
+                    }
+
+                    // follows symlinks, unlike `DirEntry::metadata`
+                    let Ok(metadata) = tokio::fs::metadata(directory.join(name)).await else {
+                        continue;
+                    };
+
+                    entries.push(DirEntry {
+                        name: name.clone(),
+                        is_dir: metadata.is_dir(),
+                        size: (!metadata.is_dir()).then_some(metadata.len()),
+                        last_modified: metadata.modified().ok(),
+                    });
+                }
+
+                Ok(Some(Listing { entries, next }))
+            }
+
+            Backend::S3 {
+                ref client,
+                ref bucket,
+                ref prefix,
+            } => {
+                let mut key_prefix = s3_key(prefix.as_deref(), &path);
+                if !key_prefix.ends_with('/') {
+                    key_prefix.push('/');
+                }
+
+                let output = client
+                    .list_objects_v2()
+                    .bucket(bucket)
+                    .prefix(&key_prefix)
+                    .delimiter("/")
+                    .max_keys(i32::try_from(limit).unwrap_or(i32::MAX))
+                    .set_continuation_token(cursor.map(String::from))
+                    .customize()
+                    .mutate_request(self.s3_user_agent())
+                    .send()
+                    .await
+                    .map_err(remi::s3::Error::from)?;
+
+                let mut entries = Vec::new();
+                for common in output.common_prefixes() {
+                    if let Some(name) = common
+                        .prefix()
+                        .and_then(|p| p.strip_prefix(&key_prefix))
+                        .map(|name| name.trim_end_matches('/'))
+                        .filter(|name| !name.is_empty())
+                    {
+                        entries.push(DirEntry {
+                            name: name.to_owned(),
+                            is_dir: true,
+                            size: None,
+                            last_modified: None,
+                        });
+                    }
+                }
+
+                for object in output.contents() {
+                    // skips the placeholder objects that some tools create for directories
+                    if let Some(name) = object
+                        .key()
+                        .and_then(|key| key.strip_prefix(&key_prefix))
+                        .filter(|name| !name.is_empty())
+                    {
+                        entries.push(DirEntry {
+                            name: name.to_owned(),
+                            is_dir: false,
+                            size: object.size().and_then(|size| size.try_into().ok()),
+                            last_modified: object.last_modified().and_then(|dt| SystemTime::try_from(*dt).ok()),
+                        });
+                    }
+                }
+
+                entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+                let next = output.next_continuation_token().map(String::from);
+                if entries.is_empty() && next.is_none() && cursor.is_none() && !path.is_empty() {
+                    return Ok(None);
+                }
+
+                Ok(Some(Listing { entries, next }))
+            }
+
+            Backend::Azure(ref container) => {
+                let mut name_prefix = azure_blob_name(&path);
+                if !name_prefix.ends_with('/') {
+                    name_prefix.push('/');
+                }
+
+                let mut request = container
+                    .list_blobs()
+                    .prefix(name_prefix.clone())
+                    .delimiter("/")
+                    .max_results(NonZeroU32::new(u32::try_from(limit).unwrap_or(u32::MAX)).unwrap())
+                    .context(self.azure_context());
+
+                if let Some(cursor) = cursor {
+                    request = request.marker(cursor);
+                }
+
+                let Some(page) = request.into_stream().next().await else {
+                    return Ok(None);
+                };
+
+                let page = page?;
+                let mut entries = Vec::new();
+                for prefix in page.blobs.prefixes() {
+                    if let Some(name) = prefix
+                        .name
+                        .strip_prefix(&name_prefix)
+                        .map(|name| name.trim_end_matches('/'))
+                        .filter(|name| !name.is_empty())
+                    {
+                        entries.push(DirEntry {
+                            name: name.to_owned(),
+                            is_dir: true,
+                            size: None,
+                            last_modified: None,
+                        });
+                    }
+                }
+
+                for blob in page.blobs.blobs() {
+                    if let Some(name) = blob.name.strip_prefix(&name_prefix).filter(|name| !name.is_empty()) {
+                        entries.push(DirEntry {
+                            name: name.to_owned(),
+                            is_dir: false,
+                            size: Some(blob.properties.content_length),
+                            last_modified: Some(blob.properties.last_modified.into()),
+                        });
+                    }
+                }
+
+                entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+                let next = page.next_marker.map(|marker| marker.as_str().to_owned());
+                if entries.is_empty() && next.is_none() && cursor.is_none() && !path.is_empty() {
+                    return Ok(None);
+                }
+
+                Ok(Some(Listing { entries, next }))
+            }
+        }
+    }
+
+    /// Returns the sorted names of the entries in `directory` on the local filesystem,
+    /// which are kept until the directory's modification time changes so that paging
+    /// through a listing doesn't read the whole directory again for every page.
+    async fn names(&self, directory: &Path) -> Result<Option<Arc<[String]>>, Error> {
+        let Backend::Filesystem { ref listings, .. } = self.backend else {
+            return Ok(None);
+        };
+
+        let modified = match tokio::fs::metadata(directory).await {
+            Ok(metadata) if metadata.is_dir() => metadata.modified().ok(),
+            Ok(_) => return Ok(None),
+            Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => {
+                return Ok(None);
+            }
+
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some(modified) = modified &&
+            let Some((cached, names)) = listings.lock().unwrap().get(directory) &&
+            *cached == modified
+        {
+            return Ok(Some(names.clone()));
+        }
+
+        let mut dir = match tokio::fs::read_dir(directory).await {
+            Ok(dir) => dir,
+            Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => {
+                return Ok(None);
+            }
+
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut names = Vec::new();
+        while let Some(entry) = dir.next_entry().await? {
+            if let Ok(name) = entry.file_name().into_string() {
+                names.push(name);
+            }
+        }
+
+        names.sort();
+        let names = Arc::<[String]>::from(names);
+
+        // a change within the same tick of the modification time wouldn't be noticed,
+        // so directories that were just modified aren't kept
+        if let Some(modified) = modified &&
+            modified.elapsed().is_ok_and(|elapsed| elapsed >= LISTINGS_SETTLE)
+        {
+            listings
+                .lock()
+                .unwrap()
+                .put(directory.to_path_buf(), (modified, names.clone()));
+        }
+
+        Ok(Some(names))
+    }
+}
+
+/// Normalizes a path from a request into a relative path with no empty or `.`
+/// components. `None` is returned if the path tries to escape with `..`.
+fn normalize(path: &str) -> Option<String> {
+    let mut components = Vec::new();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => components.push(part.to_str()?),
+            Component::CurDir | Component::RootDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(components.join("/"))
+}
+
+// Both S3 and Azure keys were historically queried as `/{path}`, so we keep doing that
+// to not break objects that are already being served.
+fn s3_key(prefix: Option<&str>, path: &str) -> String {
+    match prefix {
+        Some(prefix) if !prefix.is_empty() => {
+            format!("{}//{path}", prefix.trim_start_matches("~/").trim_start_matches("./"))
+        }
+
+        _ => format!("/{path}"),
+    }
+}
+
+fn azure_blob_name(path: &str) -> String {
+    format!("/{path}")
+}
+
+/// Returns the entity tag of a file from its inode, length and modification time (in
+/// nanoseconds), which changes whenever the file is modified or replaced without having
+/// to read it. `None` is returned if the filesystem doesn't keep modification times.
+fn file_etag(metadata: &std::fs::Metadata) -> Option<String> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    #[cfg(unix)]
+    let inode = std::os::unix::fs::MetadataExt::ino(metadata);
+
+    #[cfg(not(unix))]
+    let inode = 0;
+
+    Some(format!("\"{inode:x}-{:x}-{:x}\"", metadata.len(), modified.as_nanos()))
+}
+
+/// Some backends give entity tags without their surrounding quotes.
+fn quote_etag(etag: &str) -> String {
+    if etag.starts_with('"') || etag.starts_with("W/") {
+        return etag.to_owned();
+    }
+
+    format!("\"{etag}\"")
+}
+
+fn guess_content_type(path: &Path) -> Option<String> {
+    mime_guess::from_path(path).first_raw().map(String::from)
+}
+
+async fn sniff_content_type(path: &Path) -> io::Result<String> {
+    let mut buf = Vec::new();
+    tokio::fs::File::open(path)
+        .await?
+        .take(SNIFF_LENGTH)
+        .read_to_end(&mut buf)
+        .await?;
+
+    Ok(remi::fs::default_resolver(&buf).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("/a/b.txt").as_deref(), Some("a/b.txt"));
+        assert_eq!(normalize("a//./b.txt").as_deref(), Some("a/b.txt"));
+        assert_eq!(normalize("").as_deref(), Some(""));
+        assert_eq!(normalize("a/../../etc/passwd"), None);
+    }
+}
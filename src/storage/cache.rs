@@ -0,0 +1,367 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-memory cache of objects that sits in front of the storage backends, enabled by
+//! `[server.cache]`.
+//!
+//! Each object's metadata is cached when it is looked up, and its contents once it is
+//! read in full. Cached objects are served without asking the storage backend until
+//! they're older than the TTL, after which they are revalidated by comparing the
+//! backend's entity tag with the cached one.
+
+use super::{ByteStream, CHUNK_SIZE, Metadata};
+use crate::config::server::cache;
+use axum::body::Bytes;
+use futures_util::{Stream, stream};
+use lru::LruCache;
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    task::{Context, Poll, ready},
+    time::{Duration, Instant},
+};
+
+/// Fixed amount of bytes that every entry is accounted for on top of its path, metadata
+/// and contents.
+const ENTRY_OVERHEAD: u64 = 128;
+
+/// The in-memory object cache. Clones share the same entries, but each [`Cache::scope`]
+/// keeps the entries of a storage backend apart from every other's.
+#[derive(Clone)]
+pub struct Cache {
+    inner: Arc<Inner>,
+    scope: u32,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    scopes: AtomicU32,
+    max_size: u64,
+    max_object_size: u64,
+    ttl: Duration,
+}
+
+struct State {
+    entries: LruCache<(u32, String), Cached>,
+
+    /// Amount of bytes that the entries are accounted for.
+    size: u64,
+}
+
+struct Cached {
+    metadata: Metadata,
+    body: Option<Bytes>,
+
+    /// When the metadata was last fetched from, or confirmed by, the storage backend.
+    validated: Instant,
+}
+
+impl Cached {
+    fn cost(&self, path: &str) -> u64 {
+        ENTRY_OVERHEAD +
+            path.len() as u64 +
+            self.metadata.content_type.len() as u64 +
+            self.metadata.etag.as_ref().map_or(0, |etag| etag.len() as u64) +
+            self.body.as_ref().map_or(0, |body| body.len() as u64)
+    }
+}
+
+impl Cache {
+    /// Creates an empty cache from the `[server.cache]` table.
+    pub fn new(config: &cache::Config) -> Cache {
+        Cache {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    entries: LruCache::unbounded(),
+                    size: 0,
+                }),
+
+                scopes: AtomicU32::new(1),
+                max_size: config.max_size,
+                max_object_size: config.max_object_size,
+                ttl: Duration::from_secs(config.ttl),
+            }),
+
+            scope: 0,
+        }
+    }
+
+    /// Returns a cache that shares this one's entries and size limit, but whose entries
+    /// are kept apart from every other scope's.
+    pub fn scope(&self) -> Cache {
+        Cache {
+            inner: self.inner.clone(),
+            scope: self.inner.scopes.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    fn key(&self, path: &str) -> (u32, String) {
+        (self.scope, path.to_owned())
+    }
+
+    /// Returns the metadata of the object at `path` if it was validated within the TTL.
+    pub fn metadata(&self, path: &str) -> Option<Metadata> {
+        let mut state = self.inner.state.lock().unwrap();
+        state
+            .entries
+            .get(&self.key(path))
+            .filter(|cached| cached.validated.elapsed() < self.inner.ttl)
+            .map(|cached| cached.metadata.clone())
+    }
+
+    /// Records what the storage backend returned when looking up the object at `path`.
+    ///
+    /// If the entity tag is the same as the cached one, the cached contents are kept and
+    /// are valid for another TTL. Otherwise, the entry is replaced by one without any
+    /// contents, or removed if the object doesn't exist anymore or is too large.
+    pub fn validate(&self, path: &str, metadata: Option<&Metadata>) {
+        let key = self.key(path);
+        let mut state = self.inner.state.lock().unwrap();
+        let Some(metadata) = metadata.filter(|metadata| metadata.size <= self.inner.max_object_size) else {
+            if let Some(cached) = state.entries.pop(&key) {
+                state.size -= cached.cost(path);
+            }
+
+            return;
+        };
+
+        if let Some(cached) = state.entries.get_mut(&key) &&
+            cached.metadata.etag.is_some() &&
+            cached.metadata.etag == metadata.etag &&
+            cached.metadata.size == metadata.size
+        {
+            cached.validated = Instant::now();
+            return;
+        }
+
+        let cached = Cached {
+            metadata: metadata.clone(),
+            body: None,
+            validated: Instant::now(),
+        };
+
+        state.size += cached.cost(path);
+        if let Some(old) = state.entries.put(key, cached) {
+            state.size -= old.cost(path);
+        }
+
+        self.evict(&mut state);
+    }
+
+    /// Returns the cached contents of the object at `path`, if they were read before.
+    pub fn body(&self, path: &str) -> Option<Bytes> {
+        let mut state = self.inner.state.lock().unwrap();
+        state
+            .entries
+            .get(&self.key(path))
+            .and_then(|cached| cached.body.clone())
+    }
+
+    /// Whether if the contents of the object at `path` are cached.
+    pub fn contains(&self, path: &str) -> bool {
+        let state = self.inner.state.lock().unwrap();
+        state
+            .entries
+            .peek(&self.key(path))
+            .is_some_and(|cached| cached.body.is_some())
+    }
+
+    /// Returns the length of the object at `path` if its contents should be cached once
+    /// they're read, which is when its metadata is cached but its contents aren't.
+    pub fn wants(&self, path: &str) -> Option<u64> {
+        let state = self.inner.state.lock().unwrap();
+        state
+            .entries
+            .peek(&self.key(path))
+            .filter(|cached| cached.body.is_none())
+            .map(|cached| cached.metadata.size)
+    }
+
+    /// Caches the contents of the object at `path` if its metadata is still cached and
+    /// the contents have the length that it says.
+    fn fill(&self, path: &str, body: Bytes) {
+        let mut state = self.inner.state.lock().unwrap();
+        let Some(cached) = state.entries.peek_mut(&self.key(path)) else {
+            return;
+        };
+
+        if cached.body.is_some() || cached.metadata.size != body.len() as u64 {
+            return;
+        }
+
+        let length = body.len() as u64;
+        cached.body = Some(body);
+        state.size += length;
+
+        self.evict(&mut state);
+    }
+
+    /// Evicts the least recently used entries until the cache fits in its size limit.
+    fn evict(&self, state: &mut State) {
+        while state.size > self.inner.max_size {
+            let Some(((_, path), cached)) = state.entries.pop_lru() else {
+                break;
+            };
+
+            state.size -= cached.cost(&path);
+        }
+    }
+
+    /// Returns a stream of the cached contents of the object at `path`, from `range` if
+    /// given.
+    pub fn read(&self, path: &str, range: Option<std::ops::Range<u64>>) -> Option<ByteStream> {
+        let body = self.body(path)?;
+        let body = match range {
+            Some(range) if range.end <= body.len() as u64 => body.slice(range.start as usize..range.end as usize),
+            Some(_) => return None,
+            None => body,
+        };
+
+        let chunks = (0..body.len())
+            .step_by(CHUNK_SIZE)
+            .map(move |start| Ok(body.slice(start..(start + CHUNK_SIZE).min(body.len()))))
+            .collect::<Vec<_>>();
+
+        Some(Box::pin(stream::iter(chunks)))
+    }
+
+    /// Wraps a stream of the full contents of the object at `path`, which is `size` bytes
+    /// long, so that they're cached once all of them were read.
+    pub fn fill_from(&self, path: &str, size: u64, stream: ByteStream) -> ByteStream {
+        // empty objects have no chunks to collect
+        if size == 0 {
+            self.fill(path, Bytes::new());
+            return stream;
+        }
+
+        Box::pin(Filling {
+            cache: self.clone(),
+            path: path.to_owned(),
+            size,
+            buf: Some(Vec::with_capacity(size as usize)),
+            inner: stream,
+        })
+    }
+}
+
+/// A stream that collects the contents that pass through it, and caches them once all
+/// of them were read without an error.
+///
+/// The contents are cached as soon as `size` bytes were collected rather than when the
+/// stream ends, since the server stops polling the body once it sent `Content-Length`
+/// bytes.
+struct Filling {
+    cache: Cache,
+    path: String,
+    size: u64,
+    buf: Option<Vec<u8>>,
+    inner: ByteStream,
+}
+
+impl Stream for Filling {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let item = ready!(this.inner.as_mut().poll_next(cx));
+        match item {
+            Some(Ok(ref chunk)) => {
+                if let Some(ref mut buf) = this.buf {
+                    match buf.len() as u64 + chunk.len() as u64 <= this.size {
+                        true => buf.extend_from_slice(chunk),
+                        false => this.buf = None,
+                    }
+                }
+
+                if let Some(buf) = this.buf.take_if(|buf| buf.len() as u64 == this.size) {
+                    this.cache.fill(&this.path, Bytes::from(buf));
+                }
+            }
+
+            Some(Err(_)) => this.buf = None,
+
+            // the object is shorter than it was when it was looked up
+            None => this.buf = None,
+        }
+
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cache;
+    use crate::{config::server::cache, storage::Metadata};
+    use axum::body::Bytes;
+
+    fn metadata(etag: &str, size: u64) -> Metadata {
+        Metadata {
+            content_type: String::from("text/plain"),
+            last_modified: None,
+            etag: Some(etag.to_owned()),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_cache() {
+        let cache = Cache::new(&cache::Config {
+            max_size: 1024,
+            max_object_size: 512,
+            ttl: 60,
+        })
+        .scope();
+
+        cache.validate("a.txt", Some(&metadata("\"1\"", 5)));
+        assert!(cache.metadata("a.txt").is_some());
+        assert_eq!(cache.wants("a.txt"), Some(5));
+
+        cache.fill("a.txt", Bytes::from_static(b"hello"));
+        assert_eq!(cache.body("a.txt").as_deref(), Some(&b"hello"[..]));
+
+        // an unchanged entity tag keeps the contents, while a changed one drops them
+        cache.validate("a.txt", Some(&metadata("\"1\"", 5)));
+        assert!(cache.contains("a.txt"));
+        cache.validate("a.txt", Some(&metadata("\"2\"", 5)));
+        assert!(!cache.contains("a.txt"));
+
+        // scopes don't share entries
+        assert!(cache.scope().metadata("a.txt").is_none());
+
+        // objects that are too large aren't cached, and the least recently used entries
+        // are evicted once the cache is full
+        cache.validate("large.bin", Some(&metadata("\"3\"", 513)));
+        assert!(cache.metadata("large.bin").is_none());
+
+        cache.validate("b.bin", Some(&metadata("\"4\"", 500)));
+        cache.fill("b.bin", Bytes::from(vec![0; 500]));
+        cache.validate("c.bin", Some(&metadata("\"5\"", 500)));
+        cache.fill("c.bin", Bytes::from(vec![0; 500]));
+        assert!(cache.metadata("a.txt").is_none());
+        assert!(!cache.contains("b.bin"));
+        assert!(cache.contains("c.bin"));
+
+        cache.validate("c.bin", None);
+        assert!(cache.metadata("c.bin").is_none());
+    }
+}
@@ -0,0 +1,508 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deduplication of concurrent calls to the storage backends, enabled by
+//! `[server.coalescing]`.
+//!
+//! Concurrent lookups of the same object share one lookup from the storage backend.
+//! Concurrent reads of an object's full contents share one read as well: the first one
+//! reads the object in a task of its own and holds onto its chunks, which every read
+//! that joins it streams from the start. The reads of every storage backend can only
+//! hold up to `max_buffered_size` bytes in memory at once, past which reads aren't
+//! shared.
+
+use super::{ByteStream, Entry, Error};
+use crate::config::server::coalescing;
+use axum::body::Bytes;
+use futures_util::{
+    FutureExt, StreamExt,
+    future::{BoxFuture, Shared},
+    stream,
+};
+use lru::LruCache;
+use std::{
+    collections::HashMap,
+    io,
+    num::NonZeroUsize,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+use tokio::sync::watch;
+
+/// Amount of objects whose size from their last lookup is remembered, so that reads of
+/// them can be shared if they're small enough.
+const SIZES_CAPACITY: NonZeroUsize = NonZeroUsize::new(4096).unwrap();
+
+/// Result of a lookup, which is shared by every call that joined it.
+type Lookup = Shared<BoxFuture<'static, Result<Option<Entry>, Arc<Error>>>>;
+
+/// Deduplicates concurrent lookups and reads of a single storage backend. Calls are
+/// keyed by normalized path, so each storage backend has a [`Coalescer`] of its own.
+pub struct Coalescer {
+    max_object_size: u64,
+    budget: Budget,
+    lookups: Mutex<HashMap<String, Lookup>>,
+    reads: Arc<Mutex<HashMap<String, Arc<Flight>>>>,
+    sizes: Mutex<LruCache<String, u64>>,
+}
+
+impl Coalescer {
+    /// Creates a new [`Coalescer`] from the `[server.coalescing]` table, whose reads
+    /// are held in memory within `budget`.
+    pub fn new(config: &coalescing::Config, budget: Budget) -> Coalescer {
+        Coalescer {
+            max_object_size: config.max_object_size,
+            budget,
+            lookups: Mutex::default(),
+            reads: Arc::default(),
+            sizes: Mutex::new(LruCache::new(SIZES_CAPACITY)),
+        }
+    }
+
+    /// Looks up the object at `path` with `fetch`, or joins the lookup of it that is
+    /// already in flight. Errors are shared by their message.
+    pub async fn stat<F>(&self, path: &str, fetch: F) -> Result<Option<Entry>, Error>
+    where
+        F: Future<Output = Result<Option<Entry>, Error>> + Send + 'static,
+    {
+        let lookup = self
+            .lookups
+            .lock()
+            .unwrap()
+            .entry(path.to_owned())
+            .or_insert_with(|| fetch.map(|result| result.map_err(Arc::new)).boxed().shared())
+            .clone();
+
+        let result = lookup.clone().await;
+
+        // The first call to finish removes the lookup, unless a newer one took its place.
+        {
+            let mut lookups = self.lookups.lock().unwrap();
+            if lookups.get(path).is_some_and(|current| current.ptr_eq(&lookup)) {
+                lookups.remove(path);
+            }
+        }
+
+        drop(lookup);
+        match result {
+            Ok(entry) => {
+                let mut sizes = self.sizes.lock().unwrap();
+                match entry {
+                    Some(Entry::File(ref metadata)) => {
+                        sizes.put(path.to_owned(), metadata.size);
+                    }
+
+                    _ => {
+                        sizes.pop(path);
+                    }
+                }
+
+                Ok(entry)
+            }
+
+            Err(error) => {
+                Err(Arc::try_unwrap(error).unwrap_or_else(|error| io::Error::other(error.to_string()).into()))
+            }
+        }
+    }
+
+    /// Reads the full contents of the object at `path` with `open`, or joins the read of
+    /// them that is already in flight.
+    ///
+    /// Returns `None` without calling `open` if the object wasn't looked up beforehand,
+    /// or if it's larger than `max_object_size` or the room that is left in the budget,
+    /// since its contents are held in memory until every read that shares them is done.
+    pub async fn read<F>(&self, path: &str, open: F) -> Option<Result<Option<ByteStream>, Error>>
+    where
+        F: Future<Output = Result<Option<ByteStream>, Error>> + Send + 'static,
+    {
+        let size = self.sizes.lock().unwrap().peek(path).copied()?;
+        if size > self.max_object_size {
+            return None;
+        }
+
+        let flight = {
+            let mut reads = self.reads.lock().unwrap();
+            match reads.get(path) {
+                Some(flight) => flight.clone(),
+                None => {
+                    let reservation = self.budget.reserve(size)?;
+                    let (updates, subscriber) = watch::channel(());
+                    let flight = Arc::new(Flight {
+                        state: Mutex::default(),
+                        updates: subscriber,
+                        reservation,
+                    });
+
+                    reads.insert(path.to_owned(), flight.clone());
+                    tokio::spawn(drive(
+                        flight.clone(),
+                        updates,
+                        open,
+                        self.reads.clone(),
+                        path.to_owned(),
+                    ));
+
+                    flight
+                }
+            }
+        };
+
+        Some(flight.subscribe().await)
+    }
+}
+
+/// A read that is in flight, whose chunks are streamed to every read that joined it.
+struct Flight {
+    state: Mutex<FlightState>,
+
+    /// Changes whenever `state` does. The sender is dropped once the read is over, even
+    /// if the task that drives it panicked.
+    updates: watch::Receiver<()>,
+
+    /// Room in the budget for the object's contents, which is given back once the last
+    /// read that shares them is dropped.
+    reservation: Reservation,
+}
+
+/// Amount of bytes that the reads in flight of every [`Coalescer`] can hold in memory
+/// at once, from `max_buffered_size`.
+#[derive(Clone)]
+pub struct Budget {
+    limit: u64,
+    used: Arc<AtomicU64>,
+}
+
+impl Budget {
+    /// Creates a new [`Budget`] from the `[server.coalescing]` table.
+    pub fn new(config: &coalescing::Config) -> Budget {
+        Budget {
+            limit: config.max_buffered_size,
+            used: Arc::default(),
+        }
+    }
+
+    /// Reserves `size` bytes, or returns `None` if there isn't enough room left.
+    fn reserve(&self, size: u64) -> Option<Reservation> {
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                used.checked_add(size).filter(|used| *used <= self.limit)
+            })
+            .ok()?;
+
+        Some(Reservation {
+            used: self.used.clone(),
+            size,
+        })
+    }
+}
+
+struct Reservation {
+    used: Arc<AtomicU64>,
+    size: u64,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.used.fetch_sub(self.size, Ordering::AcqRel);
+    }
+}
+
+#[derive(Default)]
+struct FlightState {
+    /// Whether if the object exists, once the storage backend was asked for it.
+    opened: Option<Result<bool, String>>,
+    chunks: Vec<Bytes>,
+    finished: Option<Result<(), String>>,
+}
+
+enum Next {
+    Chunk(Bytes),
+    Done,
+    Failed(String),
+    Wait,
+}
+
+impl Flight {
+    async fn subscribe(self: Arc<Self>) -> Result<Option<ByteStream>, Error> {
+        let mut updates = self.updates.clone();
+        loop {
+            updates.borrow_and_update();
+            match self.state.lock().unwrap().opened.clone() {
+                Some(Ok(true)) => break,
+                Some(Ok(false)) => return Ok(None),
+                Some(Err(error)) => return Err(io::Error::other(error).into()),
+                None => {}
+            }
+
+            if updates.changed().await.is_err() && self.state.lock().unwrap().opened.is_none() {
+                return Err(io::Error::other("shared read was abandoned before it started").into());
+            }
+        }
+
+        let stream = stream::unfold(Some((self, updates, 0)), |state| async move {
+            let (flight, mut updates, cursor) = state?;
+            loop {
+                updates.borrow_and_update();
+                let next = {
+                    let state = flight.state.lock().unwrap();
+                    match (state.chunks.get(cursor), &state.finished) {
+                        (Some(chunk), _) => Next::Chunk(chunk.clone()),
+                        (None, Some(Ok(()))) => Next::Done,
+                        (None, Some(Err(error))) => Next::Failed(error.clone()),
+                        (None, None) => Next::Wait,
+                    }
+                };
+
+                match next {
+                    Next::Chunk(chunk) => return Some((Ok(chunk), Some((flight, updates, cursor + 1)))),
+                    Next::Done => return None,
+                    Next::Failed(error) => return Some((Err(io::Error::other(error)), None)),
+                    Next::Wait => {
+                        if updates.changed().await.is_err() && flight.state.lock().unwrap().finished.is_none() {
+                            return Some((
+                                Err(io::Error::other("shared read was abandoned before it finished")),
+                                None,
+                            ));
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Some(Box::pin(stream)))
+    }
+}
+
+/// Reads the object into `flight` until it is done, and then removes it from `reads`
+/// so that later reads start one of their own. The read is stopped early once every
+/// read that joined it was dropped.
+async fn drive<F>(
+    flight: Arc<Flight>,
+    updates: watch::Sender<()>,
+    open: F,
+    reads: Arc<Mutex<HashMap<String, Arc<Flight>>>>,
+    path: String,
+) where
+    F: Future<Output = Result<Option<ByteStream>, Error>>,
+{
+    let update = |f: &dyn Fn(&mut FlightState)| {
+        f(&mut flight.state.lock().unwrap());
+        updates.send_replace(());
+    };
+
+    // Reads join the flight by cloning it while `reads` is locked, so nothing can join
+    // it once only `reads` and this task hold onto it.
+    let abandoned = || {
+        let mut reads = reads.lock().unwrap();
+        let listed = reads.get(&path).is_some_and(|current| Arc::ptr_eq(current, &flight));
+        if Arc::strong_count(&flight) > 1 + usize::from(listed) {
+            return false;
+        }
+
+        if listed {
+            reads.remove(&path);
+        }
+
+        true
+    };
+
+    match open.await {
+        Ok(Some(mut stream)) => {
+            update(&|state| state.opened = Some(Ok(true)));
+            let mut received = 0;
+            let finished = loop {
+                match stream.next().await {
+                    Some(Ok(_)) if abandoned() => {
+                        break Err(String::from("every read that shared it was dropped"));
+                    }
+
+                    Some(Ok(chunk)) => {
+                        // the object can't hold more in memory than was reserved for it,
+                        // even if it grew since it was looked up
+                        received += chunk.len() as u64;
+                        if received > flight.reservation.size {
+                            break Err(String::from("object is larger than when it was looked up"));
+                        }
+
+                        update(&|state| state.chunks.push(chunk.clone()));
+                    }
+
+                    Some(Err(error)) => break Err(error.to_string()),
+                    None => break Ok(()),
+                }
+            };
+
+            update(&|state| state.finished = Some(finished.clone()));
+        }
+
+        Ok(None) => update(&|state| state.opened = Some(Ok(false))),
+        Err(error) => update(&|state| state.opened = Some(Err(error.to_string()))),
+    }
+
+    let mut reads = reads.lock().unwrap();
+    if reads.get(&path).is_some_and(|current| Arc::ptr_eq(current, &flight)) {
+        reads.remove(&path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Metadata;
+    use futures_util::{TryStreamExt, future};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_coalescing() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let config = coalescing::Config::default();
+            let coalescer = Coalescer::new(&config, Budget::new(&config));
+            let calls = Arc::new(AtomicUsize::new(0));
+
+            let stat = || {
+                let calls = calls.clone();
+                coalescer.stat("hello.txt", async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    tokio::task::yield_now().await;
+
+                    Ok(Some(Entry::File(Metadata {
+                        content_type: "text/plain".to_owned(),
+                        last_modified: None,
+                        etag: None,
+                        size: 10,
+                    })))
+                })
+            };
+
+            let (a, b) = future::join(stat(), stat()).await;
+            assert!(matches!(a, Ok(Some(Entry::File(ref metadata))) if metadata.size == 10));
+            assert!(matches!(b, Ok(Some(Entry::File(ref metadata))) if metadata.size == 10));
+            assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+            let read = || {
+                let calls = calls.clone();
+                coalescer.read("hello.txt", async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    tokio::task::yield_now().await;
+
+                    let chunks = [Ok(Bytes::from_static(b"hello")), Ok(Bytes::from_static(b"world"))];
+                    Ok(Some(Box::pin(stream::iter(chunks)) as ByteStream))
+                })
+            };
+
+            let (a, b) = future::join(read(), read()).await;
+            for stream in [a, b] {
+                let chunks: Vec<Bytes> = stream.unwrap().unwrap().unwrap().try_collect().await.unwrap();
+                assert_eq!(chunks.concat(), b"helloworld");
+            }
+
+            assert_eq!(calls.load(Ordering::SeqCst), 2);
+            assert!(coalescer.read("unknown.txt", future::pending()).await.is_none());
+        });
+    }
+
+    #[test]
+    fn test_limits() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let config = coalescing::Config {
+                max_buffered_size: 15,
+                ..Default::default()
+            };
+
+            let coalescer = Coalescer::new(&config, Budget::new(&config));
+            for path in ["a.txt", "b.txt"] {
+                let _ = coalescer
+                    .stat(path, async {
+                        Ok(Some(Entry::File(Metadata {
+                            content_type: "text/plain".to_owned(),
+                            last_modified: None,
+                            etag: None,
+                            size: 10,
+                        })))
+                    })
+                    .await;
+            }
+
+            // an endless object, whose read is only stopped once nothing reads it
+            let chunks = Arc::new(AtomicUsize::new(0));
+            let open = {
+                let chunks = chunks.clone();
+                async move {
+                    let stream = stream::unfold(chunks, |chunks| async move {
+                        tokio::task::yield_now().await;
+                        chunks.fetch_add(1, Ordering::SeqCst);
+                        Some((Ok(Bytes::from_static(b"a")), chunks))
+                    });
+
+                    Ok(Some(Box::pin(stream) as ByteStream))
+                }
+            };
+
+            let mut a = coalescer.read("a.txt", open).await.unwrap().unwrap().unwrap();
+            assert_eq!(a.next().await.unwrap().unwrap(), "a");
+
+            // both objects don't fit in the budget at once
+            assert!(coalescer.read("b.txt", future::pending()).await.is_none());
+
+            drop(a);
+            while coalescer.reads.lock().unwrap().contains_key("a.txt") {
+                tokio::task::yield_now().await;
+            }
+
+            let read = chunks.load(Ordering::SeqCst);
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+
+            // the read was stopped because nothing reads it, long before it would have
+            // outgrown its reservation
+            assert_eq!(chunks.load(Ordering::SeqCst), read);
+            assert!(read < 10);
+            assert_eq!(coalescer.budget.used.load(Ordering::SeqCst), 0);
+
+            // an object that grew since it was looked up fails instead of buffering more
+            // than was reserved for it
+            let b = coalescer.read("b.txt", async {
+                let chunks = (0..20).map(|_| Ok(Bytes::from_static(b"b")));
+                Ok(Some(Box::pin(stream::iter(chunks)) as ByteStream))
+            });
+
+            let mut b = b.await.unwrap().unwrap().unwrap();
+            let mut received = 0;
+            let error = loop {
+                match b.next().await.unwrap() {
+                    Ok(chunk) => received += chunk.len(),
+                    Err(error) => break error,
+                }
+            };
+
+            assert_eq!(received, 10);
+            assert_eq!(error.to_string(), "object is larger than when it was looked up");
+        });
+    }
+}
@@ -0,0 +1,853 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! On-disk cache of objects from S3 and Azure, enabled by `[server.disk_cache]`.
+//!
+//! Each object is kept as two files in `objects/<xx>/`: its contents, and a `.json`
+//! record with its entity tag, length and SHA-256 digest. The contents are written to
+//! `tmp/` first and only renamed into place once all of them were written, and the
+//! record is written last, so an object only counts as cached once it is complete.
+//!
+//! The directory is scanned when Hazel starts, so cached objects survive restarts.
+//! Objects that were cached by a previous run are checked against their digest while
+//! they're first read in full, and evicted if they don't match.
+
+use super::{ByteStream, CHUNK_SIZE, Metadata};
+use crate::config::server::disk_cache;
+use axum::body::Bytes;
+use eyre::Context as _;
+use futures_util::Stream;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs, io,
+    io::SeekFrom,
+    num::NonZeroUsize,
+    ops::Range,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, ready},
+    time::SystemTime,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    sync::mpsc,
+};
+use tokio_util::io::ReaderStream;
+use ulid::Ulid;
+
+const OBJECTS: &str = "objects";
+const TMP: &str = "tmp";
+
+/// Amount of chunks that can be waiting to be written to the disk. A fill is abandoned
+/// once the disk falls this far behind the client, so that the rest of the object isn't
+/// held in memory.
+const FILL_BUFFER: usize = 64;
+
+/// Amount of objects whose last lookup is remembered, so that they can be cached once
+/// they're read.
+const PENDING_CAPACITY: NonZeroUsize = NonZeroUsize::new(4096).unwrap();
+
+/// The on-disk object cache. Each [`DiskCache::scope`] keeps the objects of a storage
+/// backend apart from every other's, by the backend's location rather than by the
+/// order they were created in so that they are found again after a restart.
+#[derive(Clone)]
+pub struct DiskCache {
+    inner: Arc<Inner>,
+    origin: Arc<str>,
+}
+
+struct Inner {
+    directory: PathBuf,
+    max_size: u64,
+    max_object_size: u64,
+    state: Mutex<State>,
+}
+
+struct State {
+    entries: LruCache<String, Stored>,
+
+    /// What the last lookup of objects that aren't cached gave, which their contents
+    /// have to match to be cached.
+    pending: LruCache<String, Expected>,
+
+    /// Objects whose contents are being written right now.
+    filling: HashSet<String>,
+
+    /// Amount of bytes of contents that are cached.
+    size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Expected {
+    etag: String,
+    size: u64,
+}
+
+struct Stored {
+    etag: String,
+    size: u64,
+    sha256: String,
+
+    /// Whether if the contents were checked against the digest since Hazel started.
+    verified: bool,
+
+    /// Whether if the contents are being checked against the digest in the background.
+    verifying: bool,
+}
+
+/// The `.json` record of a cached object.
+#[derive(Serialize, Deserialize)]
+struct Record {
+    origin: String,
+    path: String,
+    etag: String,
+    size: u64,
+    sha256: String,
+}
+
+impl DiskCache {
+    /// Opens the cache directory from the `[server.disk_cache]` table and loads the
+    /// objects that are already cached in it.
+    pub fn open(config: &disk_cache::Config) -> eyre::Result<DiskCache> {
+        let directory = &config.directory;
+        let tmp = directory.join(TMP);
+
+        // anything left in `tmp/` was being written when Hazel stopped
+        if tmp.try_exists()? {
+            fs::remove_dir_all(&tmp)
+                .with_context(|| format!("failed to clear the disk cache's `{}`", tmp.display()))?;
+        }
+
+        fs::create_dir_all(&tmp)
+            .with_context(|| format!("failed to create disk cache in `{}`", directory.display()))?;
+        fs::create_dir_all(directory.join(OBJECTS))?;
+
+        let mut found = Vec::new();
+        for shard in fs::read_dir(directory.join(OBJECTS))? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(&shard)? {
+                let path = file?.path();
+                if path.extension().is_none_or(|ext| ext != "json") {
+                    // contents whose record was never written
+                    if !path.with_extension("json").exists() {
+                        let _ = fs::remove_file(&path);
+                    }
+
+                    continue;
+                }
+
+                match load(&path) {
+                    Some(loaded) => found.push(loaded),
+                    None => {
+                        warn!(record = %path.display(), "removing incomplete object from the disk cache");
+                        remove_files(&path.with_extension(""));
+                    }
+                }
+            }
+        }
+
+        // the least recently used objects go in first, so they are evicted first
+        found.sort_by_key(|(modified, ..)| *modified);
+
+        let mut state = State {
+            entries: LruCache::unbounded(),
+            pending: LruCache::new(PENDING_CAPACITY),
+            filling: HashSet::new(),
+            size: 0,
+        };
+
+        for (_, key, stored) in found {
+            state.size += stored.size;
+            state.entries.put(key, stored);
+        }
+
+        let inner = Inner {
+            directory: directory.clone(),
+            max_size: config.max_size,
+            max_object_size: config.max_object_size,
+            state: Mutex::new(state),
+        };
+
+        {
+            let mut state = inner.state.lock().unwrap();
+
+            // the cache could be smaller than it was before
+            for key in inner.evict(&mut state) {
+                remove_files(&inner.data_path(&key));
+            }
+
+            info!(
+                directory = %directory.display(),
+                objects = state.entries.len(),
+                size = state.size,
+                "opened disk cache"
+            );
+        }
+
+        Ok(DiskCache {
+            inner: Arc::new(inner),
+            origin: Arc::from(""),
+        })
+    }
+
+    /// Returns a cache that shares this one's directory and size limit, for the storage
+    /// backend at `origin`.
+    pub fn scope(&self, origin: &str) -> DiskCache {
+        DiskCache {
+            inner: self.inner.clone(),
+            origin: Arc::from(origin),
+        }
+    }
+
+    fn key(&self, path: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.origin.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(path.as_bytes());
+
+        hex::encode(hasher.finalize())
+    }
+
+    /// Records what the storage backend returned when looking up the object at `path`.
+    /// The cached object is evicted if its entity tag or length changed, or if it doesn't
+    /// exist anymore.
+    ///
+    /// Objects without an entity tag can't be checked against the backend, so they are
+    /// never cached.
+    pub fn validate(&self, path: &str, metadata: Option<&Metadata>) {
+        let key = self.key(path);
+        let expected = metadata
+            .filter(|metadata| metadata.size <= self.inner.max_object_size)
+            .and_then(|metadata| {
+                Some(Expected {
+                    etag: metadata.etag.clone()?,
+                    size: metadata.size,
+                })
+            });
+
+        let mut state = self.inner.state.lock().unwrap();
+        let stale = match (state.entries.peek(&key), &expected) {
+            (Some(stored), Some(expected)) => stored.etag != expected.etag || stored.size != expected.size,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        if stale && let Some(stored) = state.entries.pop(&key) {
+            state.size -= stored.size;
+            remove_files(&self.inner.data_path(&key));
+        }
+
+        match expected {
+            Some(expected) => state.pending.put(key, expected),
+            None => state.pending.pop(&key),
+        };
+    }
+
+    /// Whether if the object at `path` is cached.
+    pub fn contains(&self, path: &str) -> bool {
+        self.inner.state.lock().unwrap().entries.contains(&self.key(path))
+    }
+
+    /// Returns a stream of the cached contents of the object at `path`, from `range` if
+    /// given, or `None` if it isn't cached.
+    pub async fn read(&self, path: &str, range: Option<Range<u64>>) -> Option<ByteStream> {
+        let key = self.key(path);
+        let (size, sha256, verified) = {
+            let mut state = self.inner.state.lock().unwrap();
+            let stored = state.entries.get(&key)?;
+
+            (stored.size, stored.sha256.clone(), stored.verified)
+        };
+
+        // unchecked contents can only be checked by reading all of them, which requests
+        // for a range don't, so they're checked in the background instead while the
+        // range is read from the storage backend
+        let data = self.inner.data_path(&key);
+        if !verified && range.is_some() {
+            self.verify_in_background(&key, path);
+            return None;
+        }
+
+        let mut file = match tokio::fs::File::open(&data).await {
+            Ok(file) => file,
+            Err(e) => {
+                warn!(error = %e, path, "unable to open cached object, evicting it");
+                self.evict(&key);
+                return None;
+            }
+        };
+
+        // the modification time of the record is when the object was last used, which is
+        // what it is ordered by when the cache is loaded again
+        let record = data.with_extension("json");
+        tokio::task::spawn_blocking(move || {
+            let _ = fs::File::options()
+                .write(true)
+                .open(record)
+                .and_then(|file| file.set_modified(SystemTime::now()));
+        });
+
+        match range {
+            Some(range) if range.end <= size => {
+                file.seek(SeekFrom::Start(range.start)).await.ok()?;
+                Some(Box::pin(ReaderStream::with_capacity(
+                    file.take(range.end - range.start),
+                    CHUNK_SIZE,
+                )))
+            }
+
+            Some(_) => None,
+            None if verified => Some(Box::pin(ReaderStream::with_capacity(file, CHUNK_SIZE))),
+            None => Some(Box::pin(Verifying {
+                inner: Box::pin(ReaderStream::with_capacity(file.take(size), CHUNK_SIZE)),
+                hasher: Some(Sha256::new()),
+                remaining: size,
+                sha256,
+                cache: self.clone(),
+                key,
+                path: path.to_owned(),
+            })),
+        }
+    }
+
+    /// Checks the contents of the object with `key` against its digest in a task of its
+    /// own, unless that is already happening.
+    fn verify_in_background(&self, key: &str, path: &str) {
+        let sha256 = {
+            let mut state = self.inner.state.lock().unwrap();
+            let Some(stored) = state.entries.peek_mut(key) else {
+                return;
+            };
+
+            if stored.verifying {
+                return;
+            }
+
+            stored.verifying = true;
+            stored.sha256.clone()
+        };
+
+        let cache = self.clone();
+        let key = key.to_owned();
+        let path = path.to_owned();
+        tokio::spawn(async move {
+            match digest(&cache.inner.data_path(&key)).await {
+                Ok(actual) if actual == sha256 => cache.verified(&key),
+                Ok(_) => {
+                    warn!(path, "cached object doesn't match its digest, evicting it");
+                    cache.evict(&key);
+                }
+
+                Err(e) => {
+                    warn!(error = %e, path, "unable to verify cached object, evicting it");
+                    cache.evict(&key);
+                }
+            }
+        });
+    }
+
+    fn verified(&self, key: &str) {
+        if let Some(stored) = self.inner.state.lock().unwrap().entries.peek_mut(key) {
+            stored.verified = true;
+            stored.verifying = false;
+        }
+    }
+
+    /// Wraps a stream of the full contents of the object at `path` so that they're
+    /// written to the cache as they're read, if they should be cached.
+    pub fn fill_from(&self, path: &str, stream: ByteStream) -> ByteStream {
+        let key = self.key(path);
+        let expected = {
+            let mut state = self.inner.state.lock().unwrap();
+            if state.entries.contains(&key) || state.filling.contains(&key) {
+                return stream;
+            }
+
+            let Some(expected) = state.pending.peek(&key).cloned() else {
+                return stream;
+            };
+
+            state.filling.insert(key.clone());
+            expected
+        };
+
+        let (sender, receiver) = mpsc::channel(FILL_BUFFER);
+        let remaining = expected.size;
+        tokio::spawn(self.clone().write(key, path.to_owned(), expected, receiver));
+
+        Box::pin(Filling {
+            inner: stream,
+
+            // empty objects have no chunks to send
+            sender: (remaining > 0).then_some(sender),
+            remaining,
+        })
+    }
+
+    /// Writes the contents that are received from `receiver` to the cache, once the
+    /// sender is dropped.
+    async fn write(self, key: String, path: String, expected: Expected, receiver: mpsc::Receiver<Bytes>) {
+        if let Err(e) = self.try_write(&key, &path, &expected, receiver).await {
+            warn!(error = %e, path, "unable to write object to the disk cache");
+        }
+
+        self.inner.state.lock().unwrap().filling.remove(&key);
+    }
+
+    async fn try_write(
+        &self,
+        key: &str,
+        path: &str,
+        expected: &Expected,
+        mut receiver: mpsc::Receiver<Bytes>,
+    ) -> io::Result<()> {
+        let tmp = self.inner.directory.join(TMP).join(Ulid::generate().to_string());
+        let written = async {
+            let mut file = tokio::fs::File::create(&tmp).await?;
+            let mut hasher = Sha256::new();
+            let mut written = 0;
+
+            while let Some(chunk) = receiver.recv().await {
+                file.write_all(&chunk).await?;
+                hasher.update(&chunk);
+                written += chunk.len() as u64;
+            }
+
+            // the client went away before the whole object was read, or the disk couldn't
+            // keep up with it
+            if written != expected.size {
+                return Ok(None);
+            }
+
+            file.sync_all().await?;
+            Ok::<_, io::Error>(Some(hex::encode(hasher.finalize())))
+        }
+        .await;
+
+        let sha256 = match written {
+            Ok(Some(sha256)) => sha256,
+            result => {
+                let _ = tokio::fs::remove_file(&tmp).await;
+                return result.map(|_| ());
+            }
+        };
+
+        // the object could have changed while it was being read
+        if self.inner.state.lock().unwrap().pending.peek(key) != Some(expected) {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Ok(());
+        }
+
+        let data = self.inner.data_path(key);
+        let record = Record {
+            origin: self.origin.to_string(),
+            path: path.to_owned(),
+            etag: expected.etag.clone(),
+            size: expected.size,
+            sha256: sha256.clone(),
+        };
+
+        let tmp_record = tmp.with_extension("json");
+        let result = async {
+            tokio::fs::create_dir_all(data.parent().unwrap()).await?;
+            tokio::fs::rename(&tmp, &data).await?;
+            tokio::fs::write(&tmp_record, serde_json::to_vec(&record)?).await?;
+            tokio::fs::rename(&tmp_record, data.with_extension("json")).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            let _ = tokio::fs::remove_file(&tmp_record).await;
+            remove_files(&data);
+
+            return Err(e);
+        }
+
+        let evicted = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.size += expected.size;
+
+            let stored = Stored {
+                etag: expected.etag.clone(),
+                size: expected.size,
+                sha256,
+                verified: true,
+                verifying: false,
+            };
+
+            if let Some(old) = state.entries.put(key.to_owned(), stored) {
+                state.size -= old.size;
+            }
+
+            self.inner.evict(&mut state)
+        };
+
+        for key in evicted {
+            remove_files(&self.inner.data_path(&key));
+        }
+
+        debug!(path, size = expected.size, "cached object on disk");
+        Ok(())
+    }
+
+    fn evict(&self, key: &str) {
+        let mut state = self.inner.state.lock().unwrap();
+        if let Some(stored) = state.entries.pop(key) {
+            state.size -= stored.size;
+            remove_files(&self.inner.data_path(key));
+        }
+    }
+}
+
+impl Inner {
+    fn data_path(&self, key: &str) -> PathBuf {
+        self.directory.join(OBJECTS).join(&key[..2]).join(key)
+    }
+
+    /// Evicts the least recently used objects from `state` until the cache fits in its
+    /// size limit, returning their keys so that their files can be removed.
+    fn evict(&self, state: &mut State) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while state.size > self.max_size {
+            let Some((key, stored)) = state.entries.pop_lru() else {
+                break;
+            };
+
+            state.size -= stored.size;
+            evicted.push(key);
+        }
+
+        evicted
+    }
+}
+
+/// A stream that sends the contents that pass through it to the task that writes them
+/// to the cache. The sender is dropped once all of them were sent, or as soon as the
+/// stream fails, turns out to be longer than expected or gets too far ahead of the disk
+/// so that nothing is written.
+struct Filling {
+    inner: ByteStream,
+    sender: Option<mpsc::Sender<Bytes>>,
+    remaining: u64,
+}
+
+impl Stream for Filling {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let item = ready!(this.inner.as_mut().poll_next(cx));
+        match item {
+            Some(Ok(ref chunk)) if (chunk.len() as u64) <= this.remaining => {
+                this.remaining -= chunk.len() as u64;
+                if let Some(ref sender) = this.sender &&
+                    let Err(e) = sender.try_send(chunk.clone())
+                {
+                    if matches!(e, mpsc::error::TrySendError::Full(_)) {
+                        debug!("disk cache fell behind the client, abandoning fill");
+                    }
+
+                    this.sender = None;
+                }
+
+                if this.remaining == 0 {
+                    this.sender = None;
+                }
+            }
+
+            _ => this.sender = None,
+        }
+
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// A stream of cached contents that weren't checked against their digest yet, which
+/// checks them as they pass through it. The last chunk is replaced with an error if they
+/// don't match, so that the response is aborted, and the object is evicted.
+///
+/// The check happens on the last chunk rather than at the end of the stream, since the
+/// server can stop polling the body once it has sent `Content-Length` bytes.
+struct Verifying {
+    inner: ByteStream,
+    hasher: Option<Sha256>,
+    remaining: u64,
+    sha256: String,
+    cache: DiskCache,
+    key: String,
+    path: String,
+}
+
+impl Stream for Verifying {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let item = ready!(this.inner.as_mut().poll_next(cx));
+        let Some(ref mut hasher) = this.hasher else {
+            return Poll::Ready(item);
+        };
+
+        let matches = match item {
+            Some(Ok(ref chunk)) if (chunk.len() as u64) <= this.remaining => {
+                hasher.update(chunk);
+                this.remaining -= chunk.len() as u64;
+                if this.remaining > 0 {
+                    return Poll::Ready(item);
+                }
+
+                hex::encode(this.hasher.take().unwrap().finalize()) == this.sha256
+            }
+
+            Some(Err(_)) => {
+                this.hasher = None;
+                return Poll::Ready(item);
+            }
+
+            None if this.remaining == 0 => hex::encode(this.hasher.take().unwrap().finalize()) == this.sha256,
+
+            // the file is shorter than it should be
+            _ => {
+                this.hasher = None;
+                false
+            }
+        };
+
+        if matches {
+            this.cache.verified(&this.key);
+            return Poll::Ready(item);
+        }
+
+        warn!(path = this.path, "cached object doesn't match its digest, evicting it");
+        this.cache.evict(&this.key);
+        Poll::Ready(Some(Err(io::Error::other("cached object doesn't match its digest"))))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Loads the object whose record is at `path`, returning when it was last used and its
+/// key, or `None` if its record or contents are incomplete.
+fn load(path: &Path) -> Option<(SystemTime, String, Stored)> {
+    let record: Record = serde_json::from_slice(&fs::read(path).ok()?).ok()?;
+    let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+    let data = path.with_extension("");
+    if fs::metadata(&data).ok()?.len() != record.size {
+        return None;
+    }
+
+    let key = data.file_name()?.to_str()?.to_owned();
+    Some((modified, key, Stored {
+        etag: record.etag,
+        size: record.size,
+        sha256: record.sha256,
+        verified: false,
+        verifying: false,
+    }))
+}
+
+/// Removes the contents at `data` and their record.
+fn remove_files(data: &Path) {
+    let _ = fs::remove_file(data.with_extension("json"));
+    let _ = fs::remove_file(data);
+}
+
+async fn digest(path: &Path) -> io::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DiskCache;
+    use crate::{config::server::disk_cache, storage::Metadata};
+    use axum::body::Bytes;
+    use futures_util::{TryStreamExt, stream};
+    use std::fs;
+
+    fn metadata(etag: &str, size: u64) -> Metadata {
+        Metadata {
+            content_type: String::from("text/plain"),
+            last_modified: None,
+            etag: Some(etag.to_owned()),
+            size,
+        }
+    }
+
+    /// Runs `test` with the configuration of an empty cache in a directory of its own,
+    /// which can be opened as many times as needed.
+    fn with_config<F: Future>(name: &str, test: impl FnOnce(disk_cache::Config) -> F) {
+        let dir = std::env::temp_dir().join(format!("hazel-disk-cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let config = disk_cache::Config {
+            directory: dir.clone(),
+            max_size: 1024,
+            max_object_size: 512,
+        };
+
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(test(config));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn open(config: &disk_cache::Config) -> DiskCache {
+        DiskCache::open(config).unwrap().scope("s3://hazel/")
+    }
+
+    async fn collect(stream: super::ByteStream) -> Vec<u8> {
+        stream.map_ok(|chunk| chunk.to_vec()).try_concat().await.unwrap()
+    }
+
+    /// Reads `chunks` through the cache as the contents of `path`, and waits until the
+    /// task that writes them to the disk is done.
+    async fn fill(cache: &DiskCache, path: &str, chunks: Vec<Bytes>) {
+        let size = chunks.iter().map(Bytes::len).sum::<usize>();
+        cache.validate(path, Some(&metadata("\"1\"", size as u64)));
+
+        let stream = cache.fill_from(path, Box::pin(stream::iter(chunks.into_iter().map(Ok))));
+        assert_eq!(collect(stream).await.len(), size);
+
+        while cache.inner.state.lock().unwrap().filling.contains(&cache.key(path)) {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Waits until the contents of `path` were checked in the background.
+    async fn verified(cache: &DiskCache, path: &str) {
+        while cache
+            .inner
+            .state
+            .lock()
+            .unwrap()
+            .entries
+            .peek(&cache.key(path))
+            .is_some_and(|stored| stored.verifying)
+        {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    fn hello() -> Vec<Bytes> {
+        vec![Bytes::from_static(b"he"), Bytes::from_static(b"llo")]
+    }
+
+    #[test]
+    fn test_fill() {
+        with_config("fill", async |config| {
+            let cache = open(&config);
+            fill(&cache, "a.txt", hello()).await;
+
+            assert!(cache.contains("a.txt"));
+            assert_eq!(collect(cache.read("a.txt", Some(1..4)).await.unwrap()).await, b"ell");
+            assert_eq!(collect(cache.read("a.txt", None).await.unwrap()).await, b"hello");
+            assert!(cache.read("a.txt", Some(1..6)).await.is_none());
+        });
+    }
+
+    #[test]
+    fn test_fill_faster_than_disk() {
+        with_config("fill-faster-than-disk", async |config: disk_cache::Config| {
+            // contents that are read faster than they can be written aren't cached, rather
+            // than being held in memory until the disk catches up
+            let cache = open(&config);
+            fill(&cache, "b.txt", vec![Bytes::from_static(b"b"); super::FILL_BUFFER + 1]).await;
+
+            assert!(!cache.contains("b.txt"));
+            assert_eq!(fs::read_dir(config.directory.join("tmp")).unwrap().count(), 0);
+        });
+    }
+
+    #[test]
+    fn test_restart() {
+        with_config("restart", async |config| {
+            fill(&open(&config), "a.txt", hello()).await;
+
+            let cache = open(&config);
+            assert!(cache.contains("a.txt"));
+            assert!(!cache.scope("s3://other/").contains("a.txt"));
+
+            // reading all of the contents checks them against their digest as they're read
+            assert_eq!(collect(cache.read("a.txt", None).await.unwrap()).await, b"hello");
+            assert!(cache.read("a.txt", Some(1..4)).await.is_some());
+        });
+    }
+
+    #[test]
+    fn test_restart_range() {
+        with_config("restart-range", async |config| {
+            fill(&open(&config), "a.txt", hello()).await;
+
+            // ranges of contents that weren't checked yet are left to the storage backend
+            // until they're checked in the background
+            let cache = open(&config);
+            assert!(cache.read("a.txt", Some(1..4)).await.is_none());
+            verified(&cache, "a.txt").await;
+
+            assert_eq!(collect(cache.read("a.txt", Some(1..4)).await.unwrap()).await, b"ell");
+        });
+    }
+
+    #[test]
+    fn test_digest_mismatch() {
+        with_config("digest-mismatch", async |config| {
+            fill(&open(&config), "a.txt", hello()).await;
+            fill(&open(&config), "b.txt", hello()).await;
+
+            // contents that don't match their digest anymore fail the read and are evicted
+            let cache = open(&config);
+            fs::write(cache.inner.data_path(&cache.key("a.txt")), b"jello").unwrap();
+            let stream = cache.read("a.txt", None).await.unwrap();
+            assert!(stream.try_collect::<Vec<_>>().await.is_err());
+            assert!(!cache.contains("a.txt"));
+
+            // and so are they when they're checked in the background
+            fs::write(cache.inner.data_path(&cache.key("b.txt")), b"jello").unwrap();
+            assert!(cache.read("b.txt", Some(1..4)).await.is_none());
+            verified(&cache, "b.txt").await;
+            assert!(!cache.contains("b.txt"));
+        });
+    }
+}
@@ -0,0 +1,254 @@
+// 🪶 Hazel: Easy to use read-only proxy to map objects to URLs
+// Copyright 2022-2025 Noelware, LLC. <team@noelware.org>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Exports the spans that Hazel creates to an OpenTelemetry collector over OTLP, as
+//! configured by the `[tracing.opentelemetry]` table.
+
+use crate::config;
+use axum::http::HeaderMap;
+use eyre::Context as _;
+use opentelemetry::{
+    KeyValue, global,
+    propagation::Extractor,
+    trace::{TraceContextExt, TracerProvider as _},
+};
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use tracing::{Level, Span, Subscriber, level_filters::LevelFilter};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{Layer, filter::filter_fn, registry::LookupSpan};
+use url::Position;
+
+/// Crates that the exporter uses to send spans, which are never exported themselves
+/// as every export would create more spans to export.
+const EXCLUDED_TARGETS: &[&str] = &[
+    "h2",
+    "hyper",
+    "hyper_util",
+    "opentelemetry",
+    "opentelemetry_otlp",
+    "opentelemetry_sdk",
+    "reqwest",
+    "tonic",
+    "tower",
+];
+
+/// Creates a [`SdkTracerProvider`] that exports spans to the collector in the
+/// `[tracing.opentelemetry]` table and a layer that sends spans to it, and installs
+/// the W3C Trace Context propagator so that traces can be continued from the
+/// `traceparent` header of requests.
+///
+/// The provider must be [shut down](SdkTracerProvider::shutdown) before exiting so that
+/// spans that haven't been exported yet aren't lost. This has to be called inside of
+/// a Tokio runtime since the gRPC exporter needs one.
+pub fn init<S>(
+    config: &config::opentelemetry::Config,
+    level: Level,
+) -> eyre::Result<(impl Layer<S>, SdkTracerProvider)>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = match config.url.scheme() {
+        // `grpc` isn't a scheme that tonic knows about, so it is swapped for `http`
+        "grpc" => SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(format!(
+                "http://{}",
+                config.url[Position::BeforeHost..].trim_end_matches('/')
+            ))
+            .build(),
+
+        "http" | "https" => {
+            let mut url = config.url.clone();
+            if url.path() == "/" {
+                url.set_path("/v1/traces");
+            }
+
+            SpanExporter::builder()
+                .with_http()
+                .with_protocol(Protocol::HttpBinary)
+                .with_endpoint(url.as_str())
+                .build()
+        }
+
+        scheme => bail!("unsupported scheme `{scheme}` for opentelemetry url: expected `grpc`, `http` or `https`"),
+    }
+    .context("failed to create opentelemetry exporter")?;
+
+    let resource = Resource::builder()
+        .with_service_name("hazel")
+        .with_attributes([KeyValue::new("service.version", crate::version())])
+        .with_attributes(
+            config
+                .labels
+                .iter()
+                .map(|(key, value)| KeyValue::new(key.clone(), value.clone())),
+        )
+        .build();
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build();
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer("hazel"))
+        .with_filter(LevelFilter::from_level(level))
+        .with_filter(filter_fn(|meta| {
+            let krate = meta.target().split("::").next().unwrap_or_default();
+            !EXCLUDED_TARGETS.contains(&krate)
+        }));
+
+    Ok((layer, provider))
+}
+
+/// Continues the trace from the `traceparent` and `tracestate` headers in `headers`
+/// with `span`, if there is one. This does nothing unless [`init`] was called.
+pub fn continue_trace(span: &Span, headers: &HeaderMap) {
+    let cx = global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    if cx.span().span_context().is_valid() {
+        // this can only fail if the span was already started, which doesn't happen
+        // if it's called before entering it or creating any children
+        let _ = span.set_parent(cx);
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeaderExtractor;
+    use crate::{
+        config::opentelemetry::Config,
+        server::testing::{get, router, runtime},
+    };
+    use axum::http::{HeaderMap, HeaderValue, StatusCode};
+    use opentelemetry::{propagation::TextMapPropagator, trace::TraceContextExt};
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use std::{fs, time::Duration};
+    use tracing::Level;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_extract_traceparent() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+
+        let cx = TraceContextPropagator::new().extract(&HeaderExtractor(&headers));
+        let span = cx.span();
+        let context = span.span_context();
+
+        assert!(context.is_valid());
+        assert!(context.is_remote());
+        assert_eq!(context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(context.span_id().to_string(), "00f067aa0ba902b7");
+    }
+
+    /// Accepts OTLP/HTTP exports on a local port like a collector would, sending the body
+    /// of each one to the returned channel.
+    fn collector() -> (String, std::sync::mpsc::Receiver<Vec<u8>>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = BufReader::new(stream.unwrap());
+                loop {
+                    let mut length = None;
+                    let mut line = String::new();
+                    while stream.read_line(&mut line).unwrap() > 2 {
+                        if let Some((name, value)) = line.split_once(':') &&
+                            name.eq_ignore_ascii_case("content-length")
+                        {
+                            length = value.trim().parse::<usize>().ok();
+                        }
+
+                        line.clear();
+                    }
+
+                    let Some(length) = length else {
+                        break;
+                    };
+
+                    let mut body = vec![0; length];
+                    stream.read_exact(&mut body).unwrap();
+                    stream
+                        .get_mut()
+                        .write_all(b"HTTP/1.1 200 OK\r\ncontent-type: application/x-protobuf\r\ncontent-length: 0\r\n\r\n")
+                        .unwrap();
+
+                    if tx.send(body).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        (url, rx)
+    }
+
+    #[test]
+    fn test_opentelemetry_export() {
+        let (url, exports) = collector();
+        let config = Config {
+            labels: Default::default(),
+            url: url.parse().unwrap(),
+        };
+
+        let (layer, provider) = runtime().block_on(async { super::init(&config, Level::INFO) }).unwrap();
+
+        let (dir, router) = router("opentelemetry-export", "");
+        {
+            let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+            let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+            assert_eq!(
+                get(&router, "/public/a.txt", &[("traceparent", traceparent)]),
+                StatusCode::OK
+            );
+        }
+
+        provider.force_flush().unwrap();
+
+        // the export is protobuf-encoded, which keeps strings and IDs as they are
+        let trace_id = [
+            0x4b, 0xf9, 0x2f, 0x35, 0x77, 0xb3, 0x4d, 0xa6, 0xa3, 0xce, 0x92, 0x9d, 0x0e, 0x0e, 0x47, 0x36,
+        ];
+
+        let export = exports.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(export.windows(18).any(|window| window == b"hazel.http.request"));
+        assert!(export.windows(trace_id.len()).any(|window| window == trace_id));
+
+        provider.shutdown().unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}